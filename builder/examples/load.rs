@@ -1,18 +1,30 @@
 use yaml_rust::YamlLoader;
 
-use builder::colors::{Color, load_colors};
+use builder::colors::{Color, Interpolation, DEFAULT_BACKGROUND, load_colors};
 
 
 fn main() {
 
-    let path = std::env::args().into_iter().nth(1).unwrap();
+    let path = std::env::args().nth(1).unwrap();
+    let name = std::env::args().nth(2).unwrap_or_else(|| "sky_zenith".to_string());
     let data = std::fs::read_to_string(&path).unwrap();
     let docs = YamlLoader::load_from_str(&data).unwrap();
 
     let gradient_names = &docs[0];
     println!("debug {:?}", gradient_names);
 
-    let colors: Vec<Color> = load_colors(&gradient_names["layer1"]);
+    // a rough placement of the sun elevations, the rising sun on the first half of the palette
+    let elevation_angle = |elevation: f64, rising: bool| {
+        let angle = 90.0 + elevation;
+        if rising { angle } else { 360.0 - angle }
+    };
+    let colors: Vec<Color> = load_colors(
+        &gradient_names[name.as_str()],
+        &elevation_angle,
+        Interpolation::default(),
+        DEFAULT_BACKGROUND,
+    )
+    .unwrap();
 
     println!("colors {:?}", colors);
 }
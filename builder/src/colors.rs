@@ -1,10 +1,14 @@
-use palette::{Blend, Alpha, LinSrgb, LinSrgba, Gradient};
+use palette::{Blend, Alpha, FromColor, Lch, LinSrgb, LinSrgba, Mix, Oklab, Oklch, Srgb};
 use yaml_rust::Yaml;
-use const_gen::CompileConst;
 
 
-pub fn convert_alpha(source: LinSrgba) -> LinSrgb {
-    let bg = LinSrgb::<u8>::new(0x0d,0x13,0x3a).into_format();
+/// Background of the translucent colors of the palettes declaring none, the night sky blue
+pub const DEFAULT_BACKGROUND: Color = Color { r: 0x0d, g: 0x13, b: 0x3a };
+
+
+/// Flattens a translucent color over the opaque `background`
+pub fn convert_alpha(source: LinSrgba, background: Color) -> LinSrgb {
+    let bg = LinSrgb::<u8>::new(background.r, background.g, background.b).into_format();
     let bg = Alpha { color: bg, alpha: 1.0 };
     let over = source.over(bg);
     over.color
@@ -17,33 +21,90 @@ pub enum ColorType {
 }
 
 
-pub fn read_color(color_code: &str) -> ColorType {
-    let css_color = csscolorparser::parse(color_code).unwrap();
+pub fn read_color(color_code: &str) -> Option<ColorType> {
+    let css_color = csscolorparser::parse(color_code).ok()?;
 
     if css_color.a < 1.0 {
         let color = LinSrgba::<f64>::new(css_color.r,css_color.g,css_color.b,css_color.a).into_format::<f32, f32>();
-        ColorType::WithAlpha(color)
+        Some(ColorType::WithAlpha(color))
     } else {
         let color = LinSrgb::<f64>::new(css_color.r,css_color.g,css_color.b).into_format();
-        ColorType::Normal(color)
+        Some(ColorType::Normal(color))
     }
 }
 
-fn load_color(color_code: &str) -> LinSrgb {
-    let color_type = read_color(color_code);
-    match color_type {
+/// Reads the color written in `data`, erring at `key`
+fn load_color(data: &Yaml, key: &str, background: Color) -> Result<LinSrgb, PaletteError> {
+
+    let color_type = data.as_str()
+        .and_then(read_color)
+        .ok_or_else(|| PaletteError::new(key, data, "expected a CSS color"))?;
+    Ok(match color_type {
         ColorType::Normal(color) => color,
-        ColorType::WithAlpha(color) => convert_alpha(color)
-    }
+        ColorType::WithAlpha(color) => convert_alpha(color, background)
+    })
 }
 
-#[derive(CompileConst, Debug)]
+/// Parses an opaque color, any transparency of `color_code` being ignored
+pub fn parse_color(color_code: &str) -> Option<Color> {
+    let css_color = csscolorparser::parse(color_code).ok()?;
+    let [r, g, b, _] = css_color.to_rgba8();
+    Some(Color { r, g, b })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
     pub b: u8,
 }
 
+/// An invalid entry of a palette
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaletteError {
+    /// Where the entry is, from the palette, like `[2].color`
+    pub key: String,
+    /// The entry as written, empty when missing
+    pub value: String,
+    pub message: String,
+}
+
+impl PaletteError {
+    pub fn new(key: &str, value: &Yaml, message: &str) -> Self {
+        PaletteError { key: key.to_string(), value: describe(value), message: message.to_string() }
+    }
+
+    /// Places the entry under `key`
+    pub fn within(mut self, key: &str) -> Self {
+        self.key = join_key(key, &self.key);
+        self
+    }
+}
+
+/// Appends `key` to the `parent` one, either a map key or a list index like `[2]`
+pub fn join_key(parent: &str, key: &str) -> String {
+
+    if parent.is_empty() || key.is_empty() || key.starts_with('[') {
+        format!("{}{}", parent, key)
+    } else {
+        format!("{}.{}", parent, key)
+    }
+}
+
+/// Writes a YAML value back as it appears in a file
+pub fn describe(value: &Yaml) -> String {
+
+    match value {
+        Yaml::Real(v) | Yaml::String(v) => v.clone(),
+        Yaml::Integer(v) => v.to_string(),
+        Yaml::Boolean(v) => v.to_string(),
+        Yaml::Array(_) => "[...]".to_string(),
+        Yaml::Hash(_) => "{...}".to_string(),
+        Yaml::Null => "~".to_string(),
+        Yaml::Alias(_) | Yaml::BadValue => String::new(),
+    }
+}
+
 impl From<LinSrgb> for Color {
     fn from(color: LinSrgb) -> Self {
         let c = color.into_format();
@@ -55,16 +116,422 @@ impl From<LinSrgb> for Color {
     }
 }
 
-pub fn load_colors(data: &Yaml) -> Vec<Color> {
+/// Color space in which the colors of a palette are blended
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Interpolation {
+    /// Mixes the color values as they are written
+    #[default]
+    Srgb,
+    LinearSrgb,
+    Oklab,
+    Oklch,
+    Lch,
+}
+
+impl Interpolation {
+    pub fn parse(name: &str) -> Option<Interpolation> {
+        match name {
+            "srgb" => Some(Interpolation::Srgb),
+            "linear-srgb" => Some(Interpolation::LinearSrgb),
+            "oklab" => Some(Interpolation::Oklab),
+            "oklch" => Some(Interpolation::Oklch),
+            "lch" => Some(Interpolation::Lch),
+            _ => None,
+        }
+    }
+
+    /// Blends two colors holding the values as written, which are sRGB encoded
+    fn blend(&self, from: LinSrgb, to: LinSrgb, factor: f32) -> LinSrgb {
+        let from_srgb = Srgb::new(from.red, from.green, from.blue);
+        let to_srgb = Srgb::new(to.red, to.green, to.blue);
+
+        let mixed: Srgb = match self {
+            Interpolation::Srgb => return from.mix(&to, factor),
+            Interpolation::LinearSrgb => Srgb::from_linear(
+                from_srgb.into_linear().mix(&to_srgb.into_linear(), factor)
+            ),
+            Interpolation::Oklab => Srgb::from_color(
+                Oklab::from_color(from_srgb).mix(&Oklab::from_color(to_srgb), factor)
+            ),
+            Interpolation::Oklch => Srgb::from_color(
+                Oklch::from_color(from_srgb).mix(&Oklch::from_color(to_srgb), factor)
+            ),
+            Interpolation::Lch => Srgb::from_color(
+                Lch::from_color(from_srgb).mix(&Lch::from_color(to_srgb), factor)
+            ),
+        };
+
+        LinSrgb::new(mixed.red, mixed.green, mixed.blue)
+    }
+
+    /// Blends two colors, `factor` going from 0 (`from`) to 1 (`to`)
+    pub fn mix(&self, from: &Color, to: &Color, factor: f64) -> Color {
+        let from = LinSrgb::<u8>::new(from.r, from.g, from.b).into_format();
+        let to = LinSrgb::<u8>::new(to.r, to.g, to.b).into_format();
+
+        self.blend(from, to, factor as f32).into()
+    }
+}
+
+/// Curve followed by the colors from a stop of a palette to the next one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    /// Keeps the color of the stop until the next one
+    Step,
+}
+
+impl Easing {
+    pub fn parse(name: &str) -> Option<Easing> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "ease-in" => Some(Easing::EaseIn),
+            "ease-out" => Some(Easing::EaseOut),
+            "ease-in-out" => Some(Easing::EaseInOut),
+            "step" => Some(Easing::Step),
+            _ => None,
+        }
+    }
+
+    /// Progress along the curve, `t` going from 0 at a stop to 1 at the next one
+    pub fn apply(&self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+            Easing::Step => 0.0,
+        }
+    }
+}
+
+struct Stop {
+    angle: f64,
+    color: LinSrgb,
+    easing: Easing,
+}
+
+fn number(data: &Yaml) -> Option<f64> {
+    data.as_f64().or_else(|| data.as_i64().map(|v| v as f64))
+}
+
+/// Reads the stops of a keyframed palette, sorted by angle
+///
+/// A stop is placed either at an angle of the palette (`at`), or at a sun elevation
+/// (`elevation`), while it rises, sets (`sun: rising` or `sun: setting`) or both.
+fn load_stops(
+    entries: &[Yaml],
+    elevation_angle: &dyn Fn(f64, bool) -> f64,
+    background: Color,
+) -> Result<Vec<Stop>, PaletteError> {
+
+    let mut stops = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let key = format!("[{}]", i);
+        if entry.as_hash().is_none() {
+            return Err(PaletteError::new(&key, entry, "expected a stop with a `color`"));
+        }
+
+        let color = load_color(&entry["color"], &join_key(&key, "color"), background)?;
+        let easing = match &entry["ease"] {
+            Yaml::BadValue => Easing::Linear,
+            ease => ease.as_str()
+                .and_then(Easing::parse)
+                .ok_or_else(|| PaletteError::new(&join_key(&key, "ease"), ease, "unknown easing"))?,
+        };
+
+        let number_at = |name: &str| match &entry[name] {
+            Yaml::BadValue => Ok(None),
+            value => number(value)
+                .map(Some)
+                .ok_or_else(|| PaletteError::new(&join_key(&key, name), value, "expected a number")),
+        };
+        let angles = match (number_at("at")?, number_at("elevation")?) {
+            (Some(at), _) => vec![at],
+            (None, Some(elevation)) => match &entry["sun"] {
+                Yaml::BadValue => vec![elevation_angle(elevation, true), elevation_angle(elevation, false)],
+                sun => match sun.as_str() {
+                    Some("rising") => vec![elevation_angle(elevation, true)],
+                    Some("setting") => vec![elevation_angle(elevation, false)],
+                    _ => return Err(PaletteError::new(
+                        &join_key(&key, "sun"), sun, "expected `rising` or `setting`"
+                    )),
+                },
+            },
+            (None, None) => return Err(PaletteError::new(&key, entry, "stop without `at` nor `elevation`")),
+        };
+
+        for angle in angles {
+            stops.push(Stop { angle: angle.rem_euclid(360.0), color, easing });
+        }
+    }
+
+    stops.sort_by(|a, b| a.angle.total_cmp(&b.angle));
+    Ok(stops)
+}
+
+/// Samples the stops of a keyframed palette at every degree, going round from the last stop
+/// back to the first one
+fn sample_stops(stops: &[Stop], interpolation: Interpolation) -> Vec<Color> {
 
-    let gradient_colors: Vec<LinSrgb> = data.clone()
-        .into_iter()
-        .map(|d| load_color(&d.into_string().unwrap()))
-        .collect();
+    (0..360)
+    .map(|i| {
+        let angle = i as f64;
+        let next = stops.iter().position(|s| s.angle > angle).unwrap_or(0);
+        let from = &stops[(next + stops.len() - 1) % stops.len()];
+        let to = &stops[next];
 
-    let gradient = Gradient::new(gradient_colors);
+        let span = (to.angle - from.angle).rem_euclid(360.0);
+        let elapsed = (angle - from.angle).rem_euclid(360.0);
+        let t = if span > 0.0 { elapsed / span } else { 0.0 };
 
-    gradient.take(360)
-    .map(|d| d.into())
+        interpolation.blend(from.color, to.color, from.easing.apply(t) as f32).into()
+    })
     .collect()
 }
+
+/// Spreads the colors of a plain list evenly, from the first one at 0 to the last one at 359
+fn sample_list(colors: &[LinSrgb], interpolation: Interpolation) -> Vec<Color> {
+
+    let last = (colors.len() - 1) as f64;
+    (0..360)
+    .map(|i| {
+        let position = i as f64 * last / 359.0;
+        let from = position.floor() as usize;
+        let to = (from + 1).min(colors.len() - 1);
+
+        interpolation.blend(colors[from], colors[to], position.fract() as f32).into()
+    })
+    .collect()
+}
+
+/// Loads a palette, one color per degree of sun angle
+///
+/// The palette is either a plain list of colors spread evenly over the angles, or a list of
+/// stops each carrying its position, with `elevation_angle` placing the sun elevations. Either
+/// list can be given as the `colors` of a map along with its own `interpolation` and
+/// `background`, else the colors are blended with `interpolation` and the translucent ones
+/// flattened over `background`.
+pub fn load_colors(
+    data: &Yaml,
+    elevation_angle: &dyn Fn(f64, bool) -> f64,
+    interpolation: Interpolation,
+    background: Color,
+) -> Result<Vec<Color>, PaletteError> {
+
+    if data.as_hash().is_none() {
+        return load_list(data, elevation_angle, interpolation, background);
+    }
+
+    let interpolation = match &data["interpolation"] {
+        Yaml::BadValue => interpolation,
+        name => name.as_str()
+            .and_then(Interpolation::parse)
+            .ok_or_else(|| PaletteError::new("interpolation", name, "unknown color space"))?,
+    };
+    let background = match &data["background"] {
+        Yaml::BadValue => background,
+        code => code.as_str()
+            .and_then(parse_color)
+            .ok_or_else(|| PaletteError::new("background", code, "expected a CSS color"))?,
+    };
+    load_list(&data["colors"], elevation_angle, interpolation, background)
+        .map_err(|e| e.within("colors"))
+}
+
+/// Loads a palette written as a list, either of colors or of stops
+fn load_list(
+    data: &Yaml,
+    elevation_angle: &dyn Fn(f64, bool) -> f64,
+    interpolation: Interpolation,
+    background: Color,
+) -> Result<Vec<Color>, PaletteError> {
+
+    let entries = match data.as_vec() {
+        Some(entries) if !entries.is_empty() => entries,
+        _ => return Err(PaletteError::new("", data, "expected a list of colors")),
+    };
+
+    let keyframed = entries.iter().any(|entry| entry.as_hash().is_some());
+    if keyframed {
+        return Ok(sample_stops(&load_stops(entries, elevation_angle, background)?, interpolation));
+    }
+
+    let gradient_colors: Vec<LinSrgb> = entries.iter()
+        .enumerate()
+        .map(|(i, entry)| load_color(entry, &format!("[{}]", i), background))
+        .collect::<Result<_, _>>()?;
+
+    Ok(sample_list(&gradient_colors, interpolation))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tint {
+    pub color: Color,
+    pub strength: f64,
+}
+
+impl Default for Tint {
+    /// Leaves the color untouched
+    fn default() -> Self {
+        Tint { color: Color { r: 0, g: 0, b: 0 }, strength: 0.0 }
+    }
+}
+
+pub fn load_tint(data: &Yaml, background: Color) -> Result<Tint, PaletteError> {
+
+    if data["color"].is_badvalue() {
+        return Ok(Tint::default());
+    }
+
+    Ok(Tint {
+        color: load_color(&data["color"], "color", background)?.into(),
+        strength: match &data["strength"] {
+            Yaml::BadValue => 1.0,
+            strength => number(strength)
+                .ok_or_else(|| PaletteError::new("strength", strength, "expected a number"))?,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use yaml_rust::{Yaml, YamlLoader};
+
+    use super::{load_colors, Color, Easing, Interpolation, DEFAULT_BACKGROUND};
+
+    const BLACK: Color = Color { r: 0, g: 0, b: 0 };
+    const WHITE: Color = Color { r: 255, g: 255, b: 255 };
+    const RED: Color = Color { r: 255, g: 0, b: 0 };
+    const BLUE: Color = Color { r: 0, g: 0, b: 255 };
+
+    fn yaml(text: &str) -> Yaml {
+        YamlLoader::load_from_str(text).unwrap().remove(0)
+    }
+
+    /// Sun elevations placed as in the palettes of the valley, 90 degrees at the horizon
+    fn elevation_angle(elevation: f64, rising: bool) -> f64 {
+
+        let angle = 90.0 + elevation;
+        if rising { angle } else { 360.0 - angle }
+    }
+
+    fn colors(text: &str) -> Vec<Color> {
+        load_colors(&yaml(text), &elevation_angle, Interpolation::default(), DEFAULT_BACKGROUND)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_interpolation() {
+        assert_eq!(Interpolation::parse("oklch"), Some(Interpolation::Oklch));
+        assert_eq!(Interpolation::parse("linear-srgb"), Some(Interpolation::LinearSrgb));
+        assert_eq!(Interpolation::parse("hsl"), None);
+
+        // every color space goes from one end to the other
+        for interpolation in [
+            Interpolation::Srgb,
+            Interpolation::LinearSrgb,
+            Interpolation::Oklab,
+            Interpolation::Oklch,
+            Interpolation::Lch,
+        ] {
+            assert_eq!(interpolation.mix(&RED, &BLUE, 0.0), RED, "{:?}", interpolation);
+            assert_eq!(interpolation.mix(&RED, &BLUE, 1.0), BLUE, "{:?}", interpolation);
+        }
+
+        // the written values are mixed as they are, the light ones are mixed as light
+        let srgb = Interpolation::Srgb.mix(&BLACK, &WHITE, 0.5);
+        let linear = Interpolation::LinearSrgb.mix(&BLACK, &WHITE, 0.5);
+        assert_eq!(srgb.r, 128);
+        assert!(linear.r > 180, "{:?}", linear);
+    }
+
+    #[test]
+    fn test_easing() {
+        assert_eq!(Easing::parse("ease-in-out"), Some(Easing::EaseInOut));
+        assert_eq!(Easing::parse("bounce"), None);
+
+        for easing in [Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut] {
+            assert_eq!(easing.apply(0.0), 0.0, "{:?}", easing);
+            assert_eq!(easing.apply(1.0), 1.0, "{:?}", easing);
+        }
+        assert_eq!(Easing::Linear.apply(0.5), 0.5);
+        assert_eq!(Easing::EaseIn.apply(0.5), 0.25);
+        assert_eq!(Easing::EaseOut.apply(0.5), 0.75);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+        assert_eq!(Easing::Step.apply(0.9), 0.0);
+    }
+
+    #[test]
+    fn test_stops() {
+        // a plain list is spread from 0 to 359
+        let list = colors("['#ff0000', '#0000ff']");
+        assert_eq!(list.len(), 360);
+        assert_eq!((list[0], list[359]), (RED, BLUE));
+
+        // stops go round from the last one back to the first one
+        let stops = colors("[{at: 0, color: '#ff0000'}, {at: 180, color: '#0000ff'}]");
+        assert_eq!((stops[0], stops[180]), (RED, BLUE));
+        assert_eq!(stops[90], Interpolation::Srgb.mix(&RED, &BLUE, 0.5));
+        assert_eq!(stops[270], stops[90]);
+
+        // an elevation is placed on both halves, unless the sun is given
+        let stops = colors("[{at: 0, color: '#000000'}, {elevation: 0, color: '#ffffff'}]");
+        assert_eq!((stops[90], stops[270]), (WHITE, WHITE));
+        let stops = colors(
+            "[{at: 0, color: '#000000'}, {elevation: 0, sun: rising, color: '#ffffff'}]",
+        );
+        assert_eq!(stops[90], WHITE);
+        assert_ne!(stops[270], WHITE);
+
+        // a step holds its color until the next stop
+        let stops = colors(
+            "[{at: 0, color: '#ff0000', ease: step}, {at: 180, color: '#0000ff'}]",
+        );
+        assert_eq!((stops[179], stops[180]), (RED, BLUE));
+        assert_eq!(stops[270], Interpolation::Srgb.mix(&BLUE, &RED, 0.5));
+
+        let error = |text| {
+            let e = load_colors(
+                &yaml(text), &elevation_angle, Interpolation::default(), DEFAULT_BACKGROUND
+            ).unwrap_err();
+            (e.key, e.message)
+        };
+        assert_eq!(
+            error("[{color: '#ff0000'}]"),
+            ("[0]".to_string(), "stop without `at` nor `elevation`".to_string())
+        );
+        assert_eq!(
+            error("[{at: 0, color: '#ff0000', ease: bounce}]"),
+            ("[0].ease".to_string(), "unknown easing".to_string())
+        );
+        assert_eq!(
+            error("{colors: [{elevation: 0, sun: noon, color: '#ff0000'}]}"),
+            ("colors[0].sun".to_string(), "expected `rising` or `setting`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_background() {
+        // translucent colors are flattened over the night sky, unless the palette has its own
+        let list = colors("['#ffffff00', '#ffffff']");
+        assert_eq!(list[0], DEFAULT_BACKGROUND);
+
+        let list = colors("{background: '#ff0000', colors: ['#ffffff00', '#ffffff']}");
+        assert_eq!(list[0], RED);
+
+        let list = colors("{background: '#000000', colors: ['#ffffff80']}");
+        assert!(list[0].r > 100 && list[0].r < 160, "{:?}", list[0]);
+
+        let e = load_colors(
+            &yaml("{background: nope, colors: ['#ffffff']}"),
+            &elevation_angle,
+            Interpolation::default(),
+            DEFAULT_BACKGROUND,
+        ).unwrap_err();
+        assert_eq!(e.key, "background");
+    }
+}
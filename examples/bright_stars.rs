@@ -0,0 +1,165 @@
+//! Builds `src/data/stars.csv` from a star catalog
+//!
+//! Takes a catalog and an optional limiting magnitude, 6.5 by default, and prints the stars in
+//! the format of the themes. Two catalogs are read:
+//!
+//! - the `catalog` file of the Yale Bright Star Catalog, 5th revised edition (CDS V/50, fixed
+//!   width records), whose stars are named after their Bayer or Flamsteed designation or their
+//!   HR number;
+//! - the Hipparcos catalogue (ESA 1997) as `hipparcos_mag7.json` of the `star-catalog` crate,
+//!   whose stars are named after their HIP number.
+//!
+//! Stars already named in the embedded catalog keep their name. The embedded catalog is the
+//! Hipparcos one:
+//!
+//! ```sh
+//! cargo run --example bright_stars -- hipparcos_mag7.json 6.5 > src/data/stars.csv
+//! ```
+
+use std::fs;
+use std::process;
+
+const EMBEDDED_STARS: &str = include_str!("../src/data/stars.csv");
+
+/// Farthest a star of the embedded catalog can be from a catalog record to lend it its name, in
+/// degrees
+const SAME_STAR: f64 = 0.05;
+
+struct Record {
+    name: String,
+    hours: f64,
+    declination: f64,
+    magnitude: f64,
+}
+
+fn field(line: &str, from: usize, to: usize) -> &str {
+    line.get(from - 1..to.min(line.len())).unwrap_or("").trim()
+}
+
+/// Reads a record of the Yale catalog, `None` for the entries without a position
+fn parse_yale(line: &str) -> Option<Record> {
+    let number = |from, to| field(line, from, to).parse::<f64>().ok();
+
+    let hours = number(76, 77)? + number(78, 79)? / 60.0 + number(80, 83)? / 3600.0;
+    let sign = if field(line, 84, 84) == "-" {
+        -1.0
+    } else {
+        1.0
+    };
+    let declination = sign * (number(85, 86)? + number(87, 88)? / 60.0 + number(89, 90)? / 3600.0);
+    let magnitude = number(103, 107)?;
+
+    let designation = field(line, 5, 14).split_whitespace().collect::<Vec<_>>();
+    let name = if designation.is_empty() {
+        format!("HR {}", field(line, 1, 4))
+    } else {
+        designation.join(" ")
+    };
+
+    Some(Record {
+        name,
+        hours,
+        declination,
+        magnitude,
+    })
+}
+
+/// Reads the stars of the Hipparcos catalog, stored as `[id, right ascension, declination,
+/// distance, magnitude, color]` with the angles in radians
+fn parse_hipparcos(text: &[u8]) -> Result<Vec<Record>, String> {
+    #[derive(serde::Deserialize)]
+    struct Catalog {
+        stars: Vec<(u32, f64, f64, f64, f64, f64)>,
+    }
+
+    let catalog: Catalog = serde_json::from_slice(text).map_err(|e| e.to_string())?;
+    Ok(catalog
+        .stars
+        .into_iter()
+        .map(|(id, ra, dec, _, magnitude, _)| Record {
+            name: format!("HIP {}", id),
+            hours: ra.to_degrees().rem_euclid(360.0) / 15.0,
+            declination: dec.to_degrees(),
+            magnitude,
+        })
+        .collect())
+}
+
+/// Angular distance between two stars, in degrees
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (ra1, dec1) = ((a.0 * 15.0).to_radians(), a.1.to_radians());
+    let (ra2, dec2) = ((b.0 * 15.0).to_radians(), b.1.to_radians());
+    let cos = dec1.sin() * dec2.sin() + dec1.cos() * dec2.cos() * (ra1 - ra2).cos();
+    cos.clamp(-1.0, 1.0).acos().to_degrees()
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let (path, limit) = match (args.next(), args.next()) {
+        (Some(path), limit) => (path, limit.map_or(Ok(6.5), |limit| limit.parse())),
+        _ => {
+            eprintln!("usage: bright_stars <catalog> [limiting magnitude]");
+            process::exit(2);
+        }
+    };
+    let limit = limit.unwrap_or_else(|e| {
+        eprintln!("error: invalid magnitude: {}", e);
+        process::exit(2);
+    });
+    let text = fs::read(&path).unwrap_or_else(|e| {
+        eprintln!("error: cannot read {}: {}", path, e);
+        process::exit(1);
+    });
+
+    let mut named: Vec<(&str, (f64, f64))> = EMBEDDED_STARS
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').collect();
+            match fields[..] {
+                [name, hours, declination, _] => {
+                    Some((name, (hours.parse().ok()?, declination.parse().ok()?)))
+                }
+                _ => None,
+            }
+        })
+        .collect();
+
+    let (catalog, records) = if path.ends_with(".json") {
+        let records = parse_hipparcos(&text).unwrap_or_else(|e| {
+            eprintln!("error: cannot read {}: {}", path, e);
+            process::exit(1);
+        });
+        ("Hipparcos catalogue (ESA 1997)", records)
+    } else {
+        let records = String::from_utf8_lossy(&text)
+            .lines()
+            .filter_map(parse_yale)
+            .collect();
+        ("Yale Bright Star Catalog, 5th revised edition", records)
+    };
+    let mut records: Vec<Record> = records
+        .into_iter()
+        .filter(|record| record.magnitude <= limit)
+        .collect();
+    records.sort_by(|a, b| a.magnitude.total_cmp(&b.magnitude));
+
+    println!("# {}, to magnitude {}", catalog, limit);
+    println!("# generated by examples/bright_stars.rs");
+    println!("# name,right ascension (hours),declination (degrees),visual magnitude");
+    // The records go from the brightest, so the brighter star of a close pair takes the name
+    for record in records {
+        let position = (record.hours, record.declination);
+        let name = match named
+            .iter()
+            .position(|(_, star)| distance(*star, position) < SAME_STAR)
+        {
+            Some(index) => named.swap_remove(index).0,
+            None => record.name.as_str(),
+        };
+        println!(
+            "{},{:.5},{:.4},{:.2}",
+            name, record.hours, record.declination, record.magnitude
+        );
+    }
+}
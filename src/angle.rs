@@ -1,37 +1,101 @@
-use chrono::{Duration, NaiveDateTime};
-
 use crate::sunrise;
 
-pub type Angle = i64;
+pub type Angle = f64;
+
+/// Sun elevation below which the palettes stay on their darkest night colors
+const DEEP_NIGHT: f64 = -45.0;
+
+/// Angles of the palettes reached at each sun elevation while the sun rises. The setting sun
+/// goes through the same elevations on the other half of the palettes, mirrored around 180.
+const KEYFRAMES: [(f64, f64); 6] = [
+    (DEEP_NIGHT, 0.0),
+    (sunrise::ASTRONOMICAL_TWILIGHT, 45.0),
+    (sunrise::NAUTICAL_TWILIGHT, 60.0),
+    (sunrise::CIVIL_TWILIGHT, 75.0),
+    (sunrise::SUNRISE, 90.0),
+    (90.0, 180.0),
+];
+
+/// Calculates the angle of the palettes matching a sun elevation
+///
+/// # Arguments
+///
+/// * `elevation` - The apparent elevation of the sun. Expressed as degrees
+/// * `rising` - Whether the sun is rising (first half of the palettes) or setting (second half)
+pub fn elevation_angle(elevation: f64, rising: bool) -> Angle {
+    let angle = interpolate(elevation, |&(elevation, angle)| (elevation, angle));
+
+    if rising {
+        angle
+    } else {
+        (360.0 - angle) % 360.0
+    }
+}
+
+/// Where a sky stands in the palettes
+///
+/// The sun elevation has an angle on the morning half of the palettes and another on the evening
+/// half. The two are blended while the sun goes through a culmination, so that the colors move on
+/// from the morning to the evening ones without a jump.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaletteAngle {
+    /// Angle of the sun elevation on the morning half of the palettes
+    pub morning: Angle,
+    /// Weight of the evening half, from 0 in the morning to 1 in the evening
+    pub evening: f64,
+}
+
+impl PaletteAngle {
+    pub fn new(elevation: f64, evening: f64) -> Self {
+        Self {
+            morning: elevation_angle(elevation, true),
+            evening: evening.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Angle of the sun elevation on the evening half of the palettes
+    pub fn evening_angle(&self) -> Angle {
+        (360.0 - self.morning) % 360.0
+    }
 
-pub fn sun_angle(date: NaiveDateTime, latitude: f64, longitude: f64, elevation: f64) -> Angle {
-    let (sunrise, sunset) = sunrise::sun_times(date.date(), latitude, longitude, elevation);
-    println!("Sunrise: {}, Sunset: {}", sunrise, sunset);
+    /// Blends a value taken on both halves of the palettes
+    pub fn blend(&self, value: impl Fn(Angle) -> f64) -> f64 {
+        let morning = value(self.morning);
+        if self.evening <= 0.0 {
+            return morning;
+        }
+        morning + (value(self.evening_angle()) - morning) * self.evening
+    }
+}
 
-    let angle = if date < sunrise {
-        println!("morning");
-        get_angle(date, sunset - Duration::days(1), sunrise) - 90
-    } else if date > sunset {
-        println!("night");
-        (270 + get_angle(date, sunset, sunrise + Duration::days(1))) % 360
+/// Calculates the sun elevation matching an angle of the palettes, and whether it is rising
+pub fn angle_elevation(angle: Angle) -> (f64, bool) {
+    let angle = angle.rem_euclid(360.0);
+    let (morning_angle, rising) = if angle <= 180.0 {
+        (angle, true)
     } else {
-        println!("day");
-        90 + get_angle(date, sunrise, sunset)
+        (360.0 - angle, false)
     };
 
-    println!("Angle {}", angle);
-
-    angle
+    let elevation = interpolate(morning_angle, |&(elevation, angle)| (angle, elevation));
+    (elevation, rising)
 }
 
-fn get_angle(now: NaiveDateTime, min_date: NaiveDateTime, max_date: NaiveDateTime) -> Angle {
-    let delta_day: f64 = (max_date - min_date).num_seconds() as f64;
+/// Piecewise linear interpolation along `KEYFRAMES`, `key` selecting the input and output columns
+fn interpolate(value: f64, key: impl Fn(&(f64, f64)) -> (f64, f64)) -> f64 {
+    let points: Vec<(f64, f64)> = KEYFRAMES.iter().map(key).collect();
 
-    let offset: f64 = (now - min_date).num_seconds() as f64;
+    let next = points
+        .iter()
+        .position(|&(x, _)| x > value)
+        .unwrap_or(points.len() - 1)
+        .max(1);
+    let (min_x, min_y) = points[next - 1];
+    let (max_x, max_y) = points[next];
 
-    let a = offset / delta_day;
+    let a = ((value - min_x) / (max_x - min_x)).clamp(0.0, 1.0);
 
-    (a * 180.0).max(0.0).min(180.0) as i64
+    min_y + (max_y - min_y) * a
 }
 
 #[cfg(test)]
@@ -39,48 +103,107 @@ mod tests {
     use chrono::TimeZone;
     use chrono::Utc;
 
+    use crate::sky::SkyState;
+
     const NYC_LAT: f64 = 40.7128;
     const NYC_LON: f64 = 74.0060;
 
     const PARIS_LAT: f64 = 48.864716;
     const PARIS_LON: f64 = 2.349014;
 
+    const TROMSO_LAT: f64 = 69.6492;
+    const TROMSO_LON: f64 = 18.9553;
+
     #[test]
     fn test_get_angle() {
         let d = Utc.with_ymd_and_hms(2022, 1, 1, 12, 0, 0).unwrap();
-        let a = super::sun_angle(d.naive_utc(), PARIS_LAT, PARIS_LON, 1.0);
-        assert_eq!(a, 180);
+        let a = SkyState::at(d.naive_utc(), PARIS_LAT, PARIS_LON, 1.0).angle();
+        assert_eq!(a.round() as i64, 251);
 
         let d = Utc.with_ymd_and_hms(2022, 1, 1, 0, 0, 1).unwrap();
-        let a = super::sun_angle(d.naive_utc(), PARIS_LAT, PARIS_LON, 1.0);
-        assert_eq!(a, 0);
+        let a = SkyState::at(d.naive_utc(), PARIS_LAT, PARIS_LON, 1.0).angle();
+        assert_eq!(a.round() as i64, 0);
 
         let d = Utc.with_ymd_and_hms(2022, 1, 1, 23, 59, 59).unwrap();
-        let a = super::sun_angle(d.naive_utc(), PARIS_LAT, PARIS_LON, 1.0);
-        assert_eq!(a, 359);
+        let a = SkyState::at(d.naive_utc(), PARIS_LAT, PARIS_LON, 1.0).angle();
+        assert_eq!(a.round() as i64, 0);
 
         let d = Utc.with_ymd_and_hms(2022, 1, 1, 23, 0, 0).unwrap();
-        let a = super::sun_angle(d.naive_utc(), PARIS_LAT, PARIS_LON, 1.0);
-        assert_eq!(a, 348);
+        let a = SkyState::at(d.naive_utc(), PARIS_LAT, PARIS_LON, 1.0).angle();
+        assert_eq!(a.round() as i64, 0);
 
         let d = Utc.with_ymd_and_hms(2022, 11, 1, 13, 0, 0).unwrap();
-        let a = super::sun_angle(d.naive_utc(), PARIS_LAT, PARIS_LON, 1.0);
-        assert_eq!(a, 198);
+        let a = SkyState::at(d.naive_utc(), PARIS_LAT, PARIS_LON, 1.0).angle();
+        assert_eq!(a.round() as i64, 246);
 
         let d = Utc.with_ymd_and_hms(2022, 11, 1, 23, 0, 0).unwrap();
-        let a = super::sun_angle(d.naive_utc(), PARIS_LAT, PARIS_LON, 1.0);
-        assert_eq!(a, 347);
+        let a = SkyState::at(d.naive_utc(), PARIS_LAT, PARIS_LON, 1.0).angle();
+        assert_eq!(a.round() as i64, 0);
 
         let d = Utc.with_ymd_and_hms(2022, 8, 1, 13, 0, 0).unwrap();
-        let a = super::sun_angle(d.naive_utc(), PARIS_LAT, PARIS_LON, 1.0);
-        assert_eq!(a, 191);
+        let a = SkyState::at(d.naive_utc(), PARIS_LAT, PARIS_LON, 1.0).angle();
+        assert_eq!(a.round() as i64, 213);
 
         let d = Utc.with_ymd_and_hms(2022, 8, 1, 2, 0, 0).unwrap();
-        let a = super::sun_angle(d.naive_utc(), PARIS_LAT, PARIS_LON, 1.0);
-        assert_eq!(a, 40);
+        let a = SkyState::at(d.naive_utc(), PARIS_LAT, PARIS_LON, 1.0).angle();
+        assert_eq!(a.round() as i64, 46);
 
         let d = Utc.with_ymd_and_hms(2022, 8, 1, 2, 0, 0).unwrap();
-        let a = super::sun_angle(d.naive_utc(), NYC_LAT, NYC_LON, 1.0);
-        assert_eq!(a, 37);
+        let a = SkyState::at(d.naive_utc(), NYC_LAT, NYC_LON, 1.0).angle();
+        assert_eq!(a.round() as i64, 112);
+    }
+
+    #[test]
+    fn test_polar_angle() {
+        // midnight sun: low sun around midnight, high at noon, never night
+        let d = Utc.with_ymd_and_hms(2022, 6, 21, 22, 45, 0).unwrap();
+        let a = SkyState::at(d.naive_utc(), TROMSO_LAT, TROMSO_LON, 1.0).angle();
+        assert_eq!(a.round() as i64, 266);
+
+        let d = Utc.with_ymd_and_hms(2022, 6, 21, 10, 45, 0).unwrap();
+        let a = SkyState::at(d.naive_utc(), TROMSO_LAT, TROMSO_LON, 1.0).angle();
+        assert_eq!(a.round() as i64, 134);
+
+        // polar night: twilight around noon, night at midnight
+        let d = Utc.with_ymd_and_hms(2022, 12, 21, 10, 45, 0).unwrap();
+        let a = SkyState::at(d.naive_utc(), TROMSO_LAT, TROMSO_LON, 1.0).angle();
+        assert_eq!(a.round() as i64, 276);
+
+        let d = Utc.with_ymd_and_hms(2022, 12, 21, 22, 45, 0).unwrap();
+        let a = SkyState::at(d.naive_utc(), TROMSO_LAT, TROMSO_LON, 1.0).angle();
+        assert_eq!(a.round() as i64, 2);
+    }
+
+    #[test]
+    fn test_twilight_angle() {
+        // Paris at the summer solstice never reaches the astronomical night
+        let d = Utc.with_ymd_and_hms(2022, 6, 21, 3, 4, 30).unwrap();
+        let a = SkyState::at(d.naive_utc(), PARIS_LAT, PARIS_LON, 0.0).angle();
+        assert_eq!(a.round() as i64, 75);
+
+        let d = Utc.with_ymd_and_hms(2022, 6, 21, 21, 41, 30).unwrap();
+        let a = SkyState::at(d.naive_utc(), PARIS_LAT, PARIS_LON, 0.0).angle();
+        assert_eq!(a.round() as i64, 300);
+
+        let d = Utc.with_ymd_and_hms(2022, 6, 21, 23, 50, 0).unwrap();
+        let a = SkyState::at(d.naive_utc(), PARIS_LAT, PARIS_LON, 0.0).angle();
+        assert_eq!(a.round() as i64, 314);
+    }
+
+    #[test]
+    fn test_elevation_angle() {
+        assert_eq!(super::elevation_angle(-60.0, true), 0.0);
+        assert_eq!(super::elevation_angle(-6.0, true), 75.0);
+        assert_eq!(super::elevation_angle(-6.0, false), 285.0);
+        assert_eq!(super::elevation_angle(90.0, false), 180.0);
+
+        // Oslo at noon in winter: a low sun, far from the zenith colors
+        let d = Utc.with_ymd_and_hms(2022, 12, 21, 11, 15, 0).unwrap();
+        let sky = SkyState::at(d.naive_utc(), 59.9139, 10.7522, 0.0);
+        assert!((sky.elevation_deg - 6.7).abs() < 0.3, "{:?}", sky);
+        assert_eq!(sky.angle().round() as i64, 98);
+
+        assert_eq!(super::angle_elevation(75.0), (-6.0, true));
+        assert_eq!(super::angle_elevation(300.0), (-12.0, false));
     }
 }
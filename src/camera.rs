@@ -0,0 +1,91 @@
+/// How the scene is framed before it is fitted to the image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    /// Flips the scene horizontally
+    pub mirror: bool,
+    /// Moves the view to the right, in scene widths
+    pub pan_x: f64,
+    /// Magnifies the scene around its center
+    pub zoom: f64,
+    /// Turns the horizon clockwise, in degrees, zooming in just enough to keep the corners of
+    /// the scene out of view
+    pub tilt: f64,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            mirror: true,
+            pan_x: 0.0,
+            zoom: 1.0,
+            tilt: 0.0,
+        }
+    }
+}
+
+impl Camera {
+    /// The view of a scene of `width` by `height`, from and to its coordinates
+    pub fn transform(&self, width: f64, height: f64) -> usvg::Transform {
+        let (cx, cy) = (width / 2.0, height / 2.0);
+
+        let (sin, cos) = self.tilt.to_radians().sin_cos();
+        let aspect = (width / height).max(height / width);
+        let scale = self.zoom * (cos.abs() + aspect * sin.abs());
+
+        let mut transform = usvg::Transform::default();
+        transform.translate(cx - self.pan_x * width, cy);
+        transform.scale(scale, scale);
+        transform.rotate(self.tilt);
+        if self.mirror {
+            transform.scale(-1.0, 1.0);
+        }
+        transform.translate(-cx, -cy);
+        transform
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Camera;
+
+    #[test]
+    fn test_camera() {
+        let camera = Camera::default();
+        assert_eq!(
+            camera.transform(1600.0, 900.0),
+            usvg::Transform::new(-1.0, 0.0, 0.0, 1.0, 1600.0, 0.0)
+        );
+
+        let camera = Camera {
+            mirror: false,
+            pan_x: 0.25,
+            zoom: 2.0,
+            tilt: 0.0,
+        };
+        assert_eq!(
+            camera.transform(1600.0, 900.0),
+            usvg::Transform::new(2.0, 0.0, 0.0, 2.0, -1200.0, -450.0)
+        );
+
+        // the tilted scene still covers its frame
+        let camera = Camera {
+            tilt: 10.0,
+            ..Camera::default()
+        };
+        let t = camera.transform(1600.0, 900.0);
+        let det = t.a * t.d - t.b * t.c;
+        let inverse = usvg::Transform::new(
+            t.d / det,
+            -t.b / det,
+            -t.c / det,
+            t.a / det,
+            (t.c * t.f - t.d * t.e) / det,
+            (t.b * t.e - t.a * t.f) / det,
+        );
+        for (x, y) in [(0.0, 0.0), (1600.0, 0.0), (0.0, 900.0), (1600.0, 900.0)] {
+            let (x, y) = inverse.apply(x, y);
+            assert!((-1e-9..=1600.0 + 1e-9).contains(&x), "{}", x);
+            assert!((-1e-9..=900.0 + 1e-9).contains(&y), "{}", y);
+        }
+    }
+}
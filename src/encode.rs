@@ -0,0 +1,254 @@
+use image::codecs::bmp::BmpEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::codecs::pnm::{PnmEncoder, PnmSubtype, SampleEncoding};
+use image::codecs::qoi::QoiEncoder;
+use image::{ExtendedColorType, ImageEncoder, ImageError};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::theme::Color;
+
+/// JPEG quality used when none is asked for
+pub const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+#[derive(Debug)]
+pub enum EncodeError {
+    Io(std::io::Error),
+    Image(ImageError),
+    WebP(String),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncodeError::Io(e) => write!(f, "{}", e),
+            EncodeError::Image(e) => write!(f, "{}", e),
+            EncodeError::WebP(e) => write!(f, "cannot encode WebP: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+impl From<std::io::Error> for EncodeError {
+    fn from(e: std::io::Error) -> Self {
+        EncodeError::Io(e)
+    }
+}
+
+impl From<ImageError> for EncodeError {
+    fn from(e: ImageError) -> Self {
+        EncodeError::Image(e)
+    }
+}
+
+/// Image format a rasterized scene is written in, along with its settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoder {
+    /// `compression` goes from 0, stored as is, to 9, the smallest and slowest to write
+    Png {
+        compression: Option<u8>,
+    },
+    /// `quality` goes from 1 to 100
+    Jpeg {
+        quality: u8,
+    },
+    /// Lossless unless a `quality`, from 0 to 100, is given
+    WebP {
+        quality: Option<f32>,
+    },
+    Qoi,
+    Bmp,
+    /// Binary PPM, the simplest format to read
+    Ppm,
+}
+
+impl Encoder {
+    /// The encoder of a file extension, with its default settings
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => Some(Encoder::Png { compression: None }),
+            "jpg" | "jpeg" => Some(Encoder::Jpeg {
+                quality: DEFAULT_JPEG_QUALITY,
+            }),
+            "webp" => Some(Encoder::WebP { quality: None }),
+            "qoi" => Some(Encoder::Qoi),
+            "bmp" => Some(Encoder::Bmp),
+            "ppm" | "pnm" => Some(Encoder::Ppm),
+            _ => None,
+        }
+    }
+
+    /// The encoder of the extension of `path`, PNG when unknown
+    pub fn from_path(path: &Path) -> Self {
+        path.extension()
+            .and_then(|extension| Self::from_extension(&extension.to_string_lossy()))
+            .unwrap_or(Encoder::Png { compression: None })
+    }
+
+    /// Writes the pixmap, the formats without transparency showing `background` through the
+    /// translucent pixels
+    pub fn encode<W: Write>(
+        &self,
+        pixmap: &tiny_skia::Pixmap,
+        background: Color,
+        mut writer: W,
+    ) -> Result<(), EncodeError> {
+        let (width, height) = (pixmap.width(), pixmap.height());
+        let rgba: Vec<u8> = pixmap
+            .pixels()
+            .iter()
+            .flat_map(|pixel| {
+                let c = pixel.demultiply();
+                [c.red(), c.green(), c.blue(), c.alpha()]
+            })
+            .collect();
+        // the scene may not cover the whole image, depending on its fit
+        let rgb = || -> Vec<u8> {
+            let over = |c: u8, bg: u8, alpha: u8| {
+                let (c, bg, alpha) = (c as u32, bg as u32, alpha as u32);
+                ((c * alpha + bg * (255 - alpha) + 127) / 255) as u8
+            };
+            rgba.chunks_exact(4)
+                .flat_map(|c| {
+                    [
+                        over(c[0], background.r, c[3]),
+                        over(c[1], background.g, c[3]),
+                        over(c[2], background.b, c[3]),
+                    ]
+                })
+                .collect()
+        };
+
+        match *self {
+            Encoder::Png { compression } => {
+                let compression = match compression {
+                    None => CompressionType::Default,
+                    Some(0) => CompressionType::Uncompressed,
+                    Some(level) => CompressionType::Level(level.min(9)),
+                };
+                PngEncoder::new_with_quality(writer, compression, FilterType::Adaptive)
+                    .write_image(&rgba, width, height, ExtendedColorType::Rgba8)?;
+            }
+            Encoder::Jpeg { quality } => {
+                JpegEncoder::new_with_quality(writer, quality.clamp(1, 100)).write_image(
+                    &rgb(),
+                    width,
+                    height,
+                    ExtendedColorType::Rgb8,
+                )?;
+            }
+            Encoder::WebP { quality } => {
+                let encoder = webp::Encoder::from_rgba(&rgba, width, height);
+                let data = match quality {
+                    None => encoder.encode_simple(true, 100.0),
+                    Some(quality) => encoder.encode_simple(false, quality.clamp(0.0, 100.0)),
+                }
+                .map_err(|e| EncodeError::WebP(format!("{:?}", e)))?;
+
+                writer.write_all(&data)?;
+            }
+            Encoder::Qoi => {
+                QoiEncoder::new(writer).write_image(
+                    &rgba,
+                    width,
+                    height,
+                    ExtendedColorType::Rgba8,
+                )?;
+            }
+            Encoder::Bmp => {
+                BmpEncoder::new(&mut writer).write_image(
+                    &rgb(),
+                    width,
+                    height,
+                    ExtendedColorType::Rgb8,
+                )?;
+            }
+            Encoder::Ppm => {
+                PnmEncoder::new(writer)
+                    .with_subtype(PnmSubtype::Pixmap(SampleEncoding::Binary))
+                    .write_image(&rgb(), width, height, ExtendedColorType::Rgb8)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the pixmap to a file
+    pub fn save(
+        &self,
+        pixmap: &tiny_skia::Pixmap,
+        background: Color,
+        path: &Path,
+    ) -> Result<(), EncodeError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.encode(pixmap, background, &mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Encoder;
+    use crate::theme::Color;
+
+    const BACKGROUND: Color = Color {
+        r: 255,
+        g: 128,
+        b: 0,
+    };
+
+    #[test]
+    fn test_encode() {
+        let mut pixmap = tiny_skia::Pixmap::new(16, 9).unwrap();
+        pixmap.fill(tiny_skia::Color::from_rgba8(13, 19, 58, 255));
+
+        let encoded = |encoder: Encoder| {
+            let mut data = Vec::new();
+            encoder.encode(&pixmap, BACKGROUND, &mut data).unwrap();
+            data
+        };
+
+        assert!(encoded(Encoder::Png {
+            compression: Some(1)
+        })
+        .starts_with(b"\x89PNG"));
+        assert!(encoded(Encoder::Jpeg { quality: 80 }).starts_with(&[0xff, 0xd8]));
+        assert_eq!(&encoded(Encoder::WebP { quality: None })[8..12], b"WEBP");
+        assert_eq!(
+            &encoded(Encoder::WebP {
+                quality: Some(75.0)
+            })[8..12],
+            b"WEBP"
+        );
+        assert!(encoded(Encoder::Qoi).starts_with(b"qoif"));
+        assert!(encoded(Encoder::Bmp).starts_with(b"BM"));
+        assert!(encoded(Encoder::Ppm).starts_with(b"P6"));
+
+        // transparent pixels show the background in the formats without transparency, and stay
+        // transparent in the others
+        let transparent = tiny_skia::Pixmap::new(16, 9).unwrap();
+        let (mut ppm, mut qoi) = (Vec::new(), Vec::new());
+        Encoder::Ppm
+            .encode(&transparent, BACKGROUND, &mut ppm)
+            .unwrap();
+        assert_eq!(ppm[ppm.len() - 3..], [255, 128, 0]);
+        Encoder::Qoi
+            .encode(&transparent, BACKGROUND, &mut qoi)
+            .unwrap();
+        assert_eq!(qoi[12], 4, "RGBA channels");
+
+        assert_eq!(
+            Encoder::from_extension("JPG"),
+            Some(Encoder::Jpeg { quality: 90 })
+        );
+        assert_eq!(
+            Encoder::from_path(std::path::Path::new("wallpaper")),
+            Encoder::Png { compression: None }
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use crate::camera::Camera;
+use crate::render::{Margins, SCENE_HEIGHT, SCENE_WIDTH};
+
+/// How the scene fills an image of another aspect ratio
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Fit {
+    /// Fills the image, cropping what overflows
+    #[default]
+    Cover,
+    /// Shows the whole scene, leaving transparent bands
+    Contain,
+    /// Fills the image, distorting the scene
+    Stretch,
+    /// Fills the image, extending the sky and the ground of taller images rather than cropping
+    Smart,
+}
+
+/// Part of the scene kept in view when it is cropped, or where it lies when it is contained
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Anchor {
+    #[default]
+    Center,
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Position of the anchor across the image, from 0 to 1 on both axes
+    fn position(self) -> (f64, f64) {
+        match self {
+            Anchor::Center => (0.5, 0.5),
+            Anchor::Top => (0.5, 0.0),
+            Anchor::Bottom => (0.5, 1.0),
+            Anchor::Left => (0.0, 0.5),
+            Anchor::Right => (1.0, 0.5),
+            Anchor::TopLeft => (0.0, 0.0),
+            Anchor::TopRight => (1.0, 0.0),
+            Anchor::BottomLeft => (0.0, 1.0),
+            Anchor::BottomRight => (1.0, 1.0),
+        }
+    }
+}
+
+/// Where the scene lands in an image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    /// Room to render around the scene
+    pub margins: Margins,
+    /// From the rendered scene, margins included, to the pixels of the image, through the camera
+    pub transform: usvg::Transform,
+}
+
+impl Placement {
+    pub fn new(fit: Fit, anchor: Anchor, camera: &Camera, width: u32, height: u32) -> Self {
+        let (width, height) = (width as f64, height as f64);
+        let (ax, ay) = anchor.position();
+        let cover = (width / SCENE_WIDTH).max(height / SCENE_HEIGHT);
+
+        let mut margins = Margins::default();
+        let (sx, sy) = match fit {
+            Fit::Cover => (cover, cover),
+            Fit::Contain => {
+                let scale = (width / SCENE_WIDTH).min(height / SCENE_HEIGHT);
+                (scale, scale)
+            }
+            Fit::Stretch => (width / SCENE_WIDTH, height / SCENE_HEIGHT),
+            // wider images are cropped, there is no more landscape on the sides
+            Fit::Smart if height / SCENE_HEIGHT <= width / SCENE_WIDTH => (cover, cover),
+            Fit::Smart => {
+                let scale = width / SCENE_WIDTH;
+                let extra = height / scale - SCENE_HEIGHT;
+                margins = Margins {
+                    sky: extra * ay,
+                    ground: extra * (1.0 - ay),
+                };
+                (scale, scale)
+            }
+        };
+
+        let scene_height = SCENE_HEIGHT + margins.sky + margins.ground;
+        let x = (width - sx * SCENE_WIDTH) * ax;
+        let y = (height - sy * scene_height) * ay;
+
+        let mut transform = usvg::Transform::new(sx, 0.0, 0.0, sy, x, y);
+        transform.append(&camera.transform(SCENE_WIDTH, scene_height));
+
+        Placement { margins, transform }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Anchor, Fit, Placement};
+    use crate::camera::Camera;
+
+    #[test]
+    fn test_placement() {
+        let camera = Camera::default();
+
+        // a 16:9 image fits whatever the mode
+        for fit in [Fit::Cover, Fit::Contain, Fit::Stretch, Fit::Smart] {
+            let placement = Placement::new(fit, Anchor::Center, &camera, 1920, 1080);
+            assert_eq!(
+                placement.transform,
+                usvg::Transform::new(-1.2, 0.0, 0.0, 1.2, 1920.0, 0.0)
+            );
+            assert_eq!(placement.margins.sky + placement.margins.ground, 0.0);
+        }
+
+        // a 4:3 image crops the sides of the scene, or leaves bands over and under it
+        let cover = Placement::new(Fit::Cover, Anchor::Left, &camera, 1200, 900);
+        assert_eq!(
+            cover.transform,
+            usvg::Transform::new(-1.0, 0.0, 0.0, 1.0, 1600.0, 0.0)
+        );
+        let contain = Placement::new(Fit::Contain, Anchor::Bottom, &camera, 1200, 900);
+        assert_eq!(
+            contain.transform,
+            usvg::Transform::new(-0.75, 0.0, 0.0, 0.75, 1200.0, 225.0)
+        );
+
+        // a portrait image extends the sky and the ground
+        let smart = Placement::new(Fit::Smart, Anchor::Center, &camera, 1080, 1920);
+        assert_eq!(smart.margins.sky, smart.margins.ground);
+        let scale = smart.transform.d;
+        let height = (900.0 + smart.margins.sky + smart.margins.ground) * scale;
+        assert!((height - 1920.0).abs() < 1e-9);
+        let smart = Placement::new(Fit::Smart, Anchor::Bottom, &camera, 1080, 1920);
+        assert_eq!(smart.margins.ground, 0.0);
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutError {
+    monitor: String,
+    message: &'static str,
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`{}`: {}", self.monitor, self.message)
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+/// A monitor of a layout, its offset in pixels from the origin of the layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Monitor {
+    pub width: u32,
+    pub height: u32,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl FromStr for Monitor {
+    type Err = LayoutError;
+
+    /// Reads a monitor written as `WIDTHxHEIGHT+X+Y`, the way X11 writes geometries
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let error = |message| LayoutError {
+            monitor: text.to_string(),
+            message,
+        };
+
+        let offset = text
+            .find(['+', '-'])
+            .ok_or_else(|| error("expected WIDTHxHEIGHT+X+Y"))?;
+        let (size, offset) = text.split_at(offset);
+
+        let (width, height) = size
+            .split_once('x')
+            .ok_or_else(|| error("expected WIDTHxHEIGHT+X+Y"))?;
+        let width: u32 = width.parse().map_err(|_| error("invalid width"))?;
+        let height: u32 = height.parse().map_err(|_| error("invalid height"))?;
+        if width == 0 || height == 0 {
+            return Err(error("empty monitor"));
+        }
+
+        // the vertical offset starts at the second sign
+        let split = offset[1..]
+            .find(['+', '-'])
+            .ok_or_else(|| error("expected WIDTHxHEIGHT+X+Y"))?
+            + 1;
+        let (x, y) = offset.split_at(split);
+        let x: i32 = x.parse().map_err(|_| error("invalid horizontal offset"))?;
+        let y: i32 = y.parse().map_err(|_| error("invalid vertical offset"))?;
+
+        Ok(Monitor {
+            width,
+            height,
+            x,
+            y,
+        })
+    }
+}
+
+/// Monitors sharing one scene, spread over a virtual canvas that holds them all
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub monitors: Vec<Monitor>,
+}
+
+impl FromStr for Layout {
+    type Err = LayoutError;
+
+    /// Reads monitors separated by commas, such as `2560x1440+0+0,1440x2560+2560+0`
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let monitors = text
+            .split(',')
+            .map(|monitor| monitor.trim().parse())
+            .collect::<Result<Vec<Monitor>, _>>()?;
+
+        Ok(Layout { monitors })
+    }
+}
+
+impl Layout {
+    /// A layout of a single monitor
+    pub fn single(width: u32, height: u32) -> Self {
+        Layout {
+            monitors: vec![Monitor {
+                width,
+                height,
+                x: 0,
+                y: 0,
+            }],
+        }
+    }
+
+    /// The virtual canvas, the smallest one holding every monitor
+    pub fn canvas(&self) -> Monitor {
+        let left = self.monitors.iter().map(|m| m.x).min().unwrap_or(0);
+        let top = self.monitors.iter().map(|m| m.y).min().unwrap_or(0);
+        let right = self
+            .monitors
+            .iter()
+            .map(|m| m.x + m.width as i32)
+            .max()
+            .unwrap_or(0);
+        let bottom = self
+            .monitors
+            .iter()
+            .map(|m| m.y + m.height as i32)
+            .max()
+            .unwrap_or(0);
+
+        Monitor {
+            width: (right - left) as u32,
+            height: (bottom - top) as u32,
+            x: left,
+            y: top,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Layout, Monitor};
+
+    #[test]
+    fn test_layout() {
+        let layout: Layout = "2560x1440+0+0, 1440x2560+2560+0".parse().unwrap();
+        assert_eq!(
+            layout.monitors[1],
+            Monitor {
+                width: 1440,
+                height: 2560,
+                x: 2560,
+                y: 0
+            }
+        );
+        assert_eq!(
+            layout.canvas(),
+            Monitor {
+                width: 4000,
+                height: 2560,
+                x: 0,
+                y: 0
+            }
+        );
+
+        // a monitor on the left of the primary one
+        let layout: Layout = "1920x1080+0+0,1280x1024-1280+56".parse().unwrap();
+        assert_eq!(
+            layout.canvas(),
+            Monitor {
+                width: 3200,
+                height: 1080,
+                x: -1280,
+                y: 0
+            }
+        );
+
+        for invalid in ["1920x1080", "1920+0+0", "0x1080+0+0", "1920x1080+0+", ""] {
+            assert!(invalid.parse::<Layout>().is_err(), "{}", invalid);
+        }
+        assert_eq!(
+            "1920x1080+0+0,1920xtall+1920+0"
+                .parse::<Layout>()
+                .unwrap_err()
+                .to_string(),
+            "`1920xtall+1920+0`: invalid height"
+        );
+    }
+}
@@ -0,0 +1,40 @@
+//! Beautiful wallpapers of a valley under the sky of a given place and time
+//!
+//! A [`Scene`] gathers the sky, the theme of the valley and how they are framed, and draws them
+//! on images of any size, either as pixels or as vector images.
+//!
+//! ```
+//! use chrono::{TimeZone, Utc};
+//! use goldvalley::{Location, Scene};
+//!
+//! let paris = Location::new(48.864716, 2.349014, 100.0);
+//! let datetime = Utc.with_ymd_and_hms(2022, 10, 30, 17, 0, 0).unwrap();
+//!
+//! let pixmap = Scene::new(paris, datetime).render(160, 90).unwrap();
+//! assert_eq!(pixmap.width(), 160);
+//! ```
+
+pub mod angle;
+pub mod camera;
+pub mod encode;
+pub mod fit;
+pub mod layout;
+pub mod moon;
+pub mod render;
+pub mod report;
+pub mod scene;
+pub mod season;
+pub mod sky;
+pub mod solar;
+pub mod sunrise;
+pub mod theme;
+pub mod weather;
+
+pub use camera::Camera;
+pub use encode::Encoder;
+pub use fit::{Anchor, Fit};
+pub use layout::{Layout, Monitor};
+pub use scene::{Frame, Location, Scene, SizeError};
+pub use sky::SkyState;
+pub use theme::Theme;
+pub use weather::{Conditions, Weather};
@@ -1,19 +1,35 @@
-#![allow(clippy::redundant_static_lifetimes)]
-
 use chrono::{DateTime, Utc};
-use clap::Parser;
-use std::path::PathBuf;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::{Path, PathBuf};
+
+use goldvalley::encode::{self, Encoder};
+use goldvalley::sunrise::{self, SunEvents};
+use goldvalley::{moon, report, theme};
+use goldvalley::{Anchor, Camera, Fit, Frame, Layout, Monitor, Scene, SkyState, Theme, Weather};
 
 /// Generate beautiful wallpaper based on geographical position and current time
 #[derive(Parser, Debug)]
-#[command(version, long_about = None)]
+#[command(version, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Your coordinate latitude
-    #[arg(long, default_value_t = 48.864716)]
+    #[arg(
+        long,
+        default_value_t = 48.864716,
+        allow_negative_numbers = true,
+        value_parser = parse_latitude
+    )]
     lat: f64,
 
     /// Your coordinate longitude
-    #[arg(long, default_value_t = 2.349014)]
+    #[arg(
+        long,
+        default_value_t = 2.349014,
+        allow_negative_numbers = true,
+        value_parser = parse_longitude
+    )]
     lon: f64,
 
     /// Your coordinate altitude (in meters)
@@ -28,9 +44,55 @@ struct Args {
     #[arg(long, default_value_t = 1080)]
     height: u32,
 
+    /// How the scene fills an output of another aspect ratio
+    #[arg(long, value_enum, default_value_t = Fit::Cover)]
+    fit: Fit,
+
+    /// Part of the scene kept in view when it is cropped, or where it lies when it is contained
+    #[arg(long, value_enum, default_value_t = Anchor::Center)]
+    anchor: Anchor,
+
+    /// Flip the scene horizontally, as it has always been drawn
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    mirror: bool,
+
+    /// Move the view to the right, in scene widths (negative values move it to the left)
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    pan_x: f64,
+
+    /// Magnify the scene around its center
+    #[arg(long, default_value_t = 1.0, value_parser = parse_zoom)]
+    zoom: f64,
+
+    /// Turn the horizon clockwise, in degrees, zooming in to keep the corners covered
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    tilt_horizon: f64,
+
+    /// Monitors the scene spans, such as `2560x1440+0+0,1440x2560+2560+0`, each one written to
+    /// the output path numbered after its position in the layout (overwrite width and height)
+    #[arg(long)]
+    layout: Option<Layout>,
+
+    /// Also write the whole layout to the output path
+    #[arg(long, requires = "layout")]
+    combined: bool,
+
     /// Path for the output image
-    #[arg(short, long, value_name = "FILE")]
-    output: PathBuf,
+    #[arg(short, long, value_name = "FILE", required = true)]
+    output: Option<PathBuf>,
+
+    /// Format of the output image, guessed from its extension when not given. An SVG image
+    /// keeps the scene resolution-independent
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+
+    /// Quality of a JPEG image, or of a WebP image made lossy (WebP images are lossless without it)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: Option<u8>,
+
+    /// Compression of a PNG image, from 0 (none) to 9 (the smallest, the slowest to write)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=9))]
+    compression: Option<u8>,
 
     /// Force sun angle (overwrite lat, lon, alt)
     #[arg(long)]
@@ -40,46 +102,350 @@ struct Args {
     #[arg(long)]
     #[arg(value_parser = parse_hour)]
     datetime: Option<DateTime<Utc>>,
+
+    /// Weather drawn over the scene
+    #[arg(long, value_enum, default_value_t = Weather::Clear)]
+    weather: Weather,
+
+    /// Seed of the weather, the same seed always draws the same clouds
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Weather report drawn over the scene, a METAR observation or a JSON file (overwrite weather)
+    #[arg(long, value_name = "FILE")]
+    weather_file: Option<PathBuf>,
+
+    /// Report more of what is going on, on the standard error (repeat for more details)
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Report only the errors, or nothing at all when repeated
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    quiet: u8,
+
+    /// Theme of the scene, either the name of a built-in theme (mountains, sea, desert, city) or
+    /// the directory of a theme pack
+    #[arg(long, default_value = theme::DEFAULT_THEME)]
+    theme: String,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Png,
+    Jpeg,
+    Webp,
+    Qoi,
+    Bmp,
+    Ppm,
+    Svg,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Work on theme packs
+    Theme {
+        #[command(subcommand)]
+        command: ThemeCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ThemeCommand {
+    /// Validate a theme pack, reporting the file, line and entry of the first error found
+    Check {
+        /// Directory of the theme pack
+        dir: PathBuf,
+    },
 }
 
-pub mod angle;
-pub mod render;
-pub mod sunrise;
+/// Writes the log records to the standard error, the standard output being left to the results
+struct Logger;
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            log::Level::Error => eprintln!("error: {}", record.args()),
+            log::Level::Warn => eprintln!("warning: {}", record.args()),
+            log::Level::Info => eprintln!("{}", record.args()),
+            level => eprintln!("[{}] {}: {}", level, record.target(), record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Logger = Logger;
+
+/// Warnings and errors are reported by default, each `-v` adds a level and each `-q` removes one
+fn init_logger(verbose: u8, quiet: u8) {
+    let levels = [
+        log::LevelFilter::Off,
+        log::LevelFilter::Error,
+        log::LevelFilter::Warn,
+        log::LevelFilter::Info,
+        log::LevelFilter::Debug,
+        log::LevelFilter::Trace,
+    ];
+    let level = (2 + verbose as i32 - quiet as i32).clamp(0, levels.len() as i32 - 1);
+
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(levels[level as usize]);
+}
 
 fn parse_hour(arg: &str) -> Result<DateTime<Utc>, chrono::format::ParseError> {
     let datetime = DateTime::parse_from_rfc3339(arg)?;
     Ok(datetime.into())
 }
 
+fn parse_zoom(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(zoom) if zoom > 0.0 && zoom.is_finite() => Ok(zoom),
+        Ok(_) => Err("expected a positive factor".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_degrees(arg: &str, limit: f64) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(degrees) if (-limit..=limit).contains(&degrees) => Ok(degrees),
+        Ok(_) => Err(format!("expected degrees from -{} to {}", limit, limit)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_latitude(arg: &str) -> Result<f64, String> {
+    parse_degrees(arg, 90.0)
+}
+
+fn parse_longitude(arg: &str) -> Result<f64, String> {
+    parse_degrees(arg, 180.0)
+}
+
+/// Encoder of the output image, `None` for an SVG image
+fn output_encoder(args: &Args, output: &Path) -> Option<Encoder> {
+    let format = args.format.or_else(|| {
+        let extension = output.extension()?.to_str()?;
+        Format::from_str(extension, true).ok()
+    });
+
+    let encoder = match format {
+        Some(Format::Svg) => {
+            ignore_quality(args, output);
+            return None;
+        }
+        Some(Format::Png) => Encoder::Png { compression: None },
+        Some(Format::Jpeg) => Encoder::Jpeg {
+            quality: encode::DEFAULT_JPEG_QUALITY,
+        },
+        Some(Format::Webp) => Encoder::WebP { quality: None },
+        Some(Format::Qoi) => Encoder::Qoi,
+        Some(Format::Bmp) => Encoder::Bmp,
+        Some(Format::Ppm) => Encoder::Ppm,
+        None => Encoder::from_path(output),
+    };
+
+    Some(match encoder {
+        Encoder::Png { .. } => {
+            ignore_quality(args, output);
+            Encoder::Png {
+                compression: args.compression,
+            }
+        }
+        Encoder::Jpeg { quality } => Encoder::Jpeg {
+            quality: args.quality.unwrap_or(quality),
+        },
+        Encoder::WebP { .. } => Encoder::WebP {
+            quality: args.quality.map(f32::from),
+        },
+        encoder => {
+            ignore_quality(args, output);
+            encoder
+        }
+    })
+}
+
+/// Warns that `--quality` has no effect on an output neither JPEG nor WebP
+fn ignore_quality(args: &Args, output: &Path) {
+    if args.quality.is_some() {
+        log::warn!(
+            "--quality only applies to JPEG and WebP images, ignored for {}",
+            output.display()
+        );
+    }
+}
+
+/// Path of the image of the `number`th monitor, such as `wallpaper-2.png`
+fn monitor_path(output: &Path, number: usize) -> PathBuf {
+    let mut name = output.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!("-{}", number));
+    if let Some(extension) = output.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    output.with_file_name(name)
+}
+
+/// Writes the part of the frame seen by `monitor`, the frame covering `canvas`
+fn write_image(
+    frame: &Frame,
+    encoder: Option<Encoder>,
+    canvas: Monitor,
+    monitor: Monitor,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (x, y) = (monitor.x - canvas.x, monitor.y - canvas.y);
+
+    match encoder {
+        Some(encoder) => {
+            let pixmap = frame.render_part(x, y, monitor.width, monitor.height)?;
+            encoder.save(&pixmap, frame.background(), path)?;
+        }
+        None => {
+            let tree = frame.tree_part(x, y, monitor.width, monitor.height)?;
+            std::fs::write(path, tree.to_string(&usvg::XmlOptions::default()))?;
+        }
+    }
+    Ok(())
+}
+
+fn check_theme(dir: &Path) {
+    match Theme::check(dir) {
+        Ok(theme) => {
+            println!(
+                "{}: {} layers, {} landscape paths, {} fog banks, {} stars",
+                dir.display(),
+                theme.palette.layers.len(),
+                theme.landscape.len(),
+                theme.fog.len(),
+                theme.stars.len()
+            );
+            if !theme.built_in.is_empty() {
+                println!("Built-in files used: {}", theme.built_in.join(", "));
+            }
+        }
+        Err(e) => {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
 fn main() {
     let args = Args::parse();
+    init_logger(args.verbose, args.quiet);
 
-    let angle = if let Some(angle) = args.angle {
-        angle
+    if let Some(Command::Theme {
+        command: ThemeCommand::Check { dir },
+    }) = &args.command
+    {
+        check_theme(dir);
+        return;
+    }
+
+    let theme = match Theme::select(&args.theme) {
+        Ok(theme) => theme,
+        Err(e) => {
+            log::error!("Cannot load theme: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let sky = if let Some(angle) = args.angle {
+        SkyState::from_angle(angle as f64)
     } else {
         let datetime = args.datetime.unwrap_or_else(Utc::now);
-        println!("Datetime: {}", datetime);
+        log::info!("Datetime: {}", datetime);
+
+        let events = sunrise::sun_times_at(datetime.naive_utc(), args.lat, args.lon, args.alt);
+        if let SunEvents::Normal { rise, set } = events {
+            log::info!("Sunrise: {}, Sunset: {}", rise, set);
+        }
 
-        angle::sun_angle(datetime.naive_utc(), args.lat, args.lon, args.alt)
-            .try_into()
-            .unwrap()
+        let moon_times = moon::moon_times(datetime.date_naive(), args.lat, args.lon);
+        log::info!(
+            "Moonrise: {:?}, Moonset: {:?}",
+            moon_times.rise,
+            moon_times.set
+        );
+
+        let sky = SkyState::at(datetime.naive_utc(), args.lat, args.lon, args.alt);
+        log::info!(
+            "Sun elevation {:.2}, {:?}, angle {:.2}",
+            sky.elevation_deg,
+            sky.phase,
+            sky.angle()
+        );
+        if let Some(moon) = &sky.moon {
+            log::info!(
+                "Moon altitude {:.2}, illuminated {:.0}%",
+                moon.altitude,
+                moon.illuminated_fraction * 100.0
+            );
+        }
+
+        sky
+    };
+    let weather = match &args.weather_file {
+        Some(path) => match report::load(path) {
+            Ok(conditions) => conditions,
+            Err(e) => {
+                log::error!("Cannot read weather report {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        },
+        None => args.weather.into(),
+    };
+
+    let camera = Camera {
+        mirror: args.mirror,
+        pan_x: args.pan_x,
+        zoom: args.zoom,
+        tilt: args.tilt_horizon,
+    };
+    let scene = Scene::from_sky(sky)
+        .with_theme(theme)
+        .with_weather(weather, args.seed)
+        .with_fit(args.fit, args.anchor)
+        .with_camera(camera);
+
+    // the scene is fitted to the whole layout, each monitor showing its part of it
+    let layout = args
+        .layout
+        .clone()
+        .unwrap_or_else(|| Layout::single(args.width, args.height));
+    let canvas = layout.canvas();
+    let frame = match scene.frame(canvas.width, canvas.height) {
+        Ok(frame) => frame,
+        Err(e) => {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }
     };
 
-    let image = render::render(angle as usize);
-
-    let sx = -1.0;
-    let sy = 1.0;
-    let cx = args.width as f32 / 2.0;
-    let cy = args.height as f32 / 2.0;
-    let transform = tiny_skia::Transform::from_row(sx, 0.0, 0.0, sy, cx - sx * cx, cy - sy * cy);
-
-    let mut pixmap = tiny_skia::Pixmap::new(args.width, args.height).unwrap();
-    resvg::render(
-        &image,
-        usvg::FitTo::Width(args.width),
-        transform,
-        pixmap.as_mut(),
-    )
-    .unwrap();
-    pixmap.save_png(args.output).unwrap();
+    let output = args.output.clone().unwrap();
+    let encoder = output_encoder(&args, &output);
+    let mut images = Vec::new();
+    if args.layout.is_none() || args.combined {
+        images.push((output.clone(), canvas));
+    }
+    if args.layout.is_some() {
+        for (i, monitor) in layout.monitors.iter().enumerate() {
+            images.push((monitor_path(&output, i + 1), *monitor));
+        }
+    }
+
+    for (path, monitor) in images {
+        if let Err(e) = write_image(&frame, encoder, canvas, monitor, &path) {
+            log::error!("Cannot write {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+        log::info!("Wrote {}", path.display());
+    }
 }
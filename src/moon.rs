@@ -0,0 +1,244 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+use crate::solar;
+
+const JULIAN_J2000: f64 = 2451545.0;
+const EARTH_RADIUS_KM: f64 = 6378.14;
+
+/// Apparent altitude of the moon center at moonrise and moonset, refraction and radius included
+const MOONRISE: f64 = -0.833;
+
+/// Position and phase of the moon in the local sky
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoonPosition {
+    /// Apparent altitude above the horizon, corrected for parallax and refraction. Expressed as degrees
+    pub altitude: f64,
+    /// Azimuth measured clockwise from the north. Expressed as degrees in `[0, 360)`
+    pub azimuth: f64,
+    /// Fraction of the disc lit by the sun, from 0 (new moon) to 1 (full moon)
+    pub illuminated_fraction: f64,
+    /// Age in the lunation, from 0 (new moon) through 0.5 (full moon) back to 1
+    pub phase: f64,
+}
+
+impl MoonPosition {
+    /// Whether the lit part of the moon is growing, i.e. before the full moon
+    pub fn is_waxing(&self) -> bool {
+        self.phase < 0.5
+    }
+}
+
+/// Moonrise and moonset of a single day, the moon may skip either of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoonEvents {
+    pub rise: Option<NaiveDateTime>,
+    pub set: Option<NaiveDateTime>,
+}
+
+/// Geocentric coordinates of the moon
+struct LunarCoordinates {
+    right_ascension: f64,
+    declination: f64,
+    /// Horizontal parallax, in degrees
+    parallax: f64,
+    /// Mean elongation from the sun, in degrees
+    elongation: f64,
+    /// Phase angle seen from the earth, in degrees
+    phase_angle: f64,
+}
+
+fn sin(degrees: f64) -> f64 {
+    degrees.to_radians().sin()
+}
+
+fn cos(degrees: f64) -> f64 {
+    degrees.to_radians().cos()
+}
+
+fn lunar_coordinates(datetime: NaiveDateTime) -> LunarCoordinates {
+    //see Jean Meeus, Astronomical Algorithms, chapters 47 and 48, main periodic terms only
+
+    let t = (solar::julian_day(datetime) - JULIAN_J2000) / 36525.0;
+
+    let mean_longitude = 218.3164477 + 481267.88123421 * t;
+    let d = 297.8501921 + 445267.1114034 * t;
+    let m = 357.5291092 + 35999.0502909 * t;
+    let mp = 134.9633964 + 477198.8675055 * t;
+    let f = 93.2720950 + 483202.0175233 * t;
+
+    let longitude = mean_longitude
+        + 6.288774 * sin(mp)
+        + 1.274027 * sin(2.0 * d - mp)
+        + 0.658314 * sin(2.0 * d)
+        + 0.213618 * sin(2.0 * mp)
+        - 0.185116 * sin(m)
+        - 0.114332 * sin(2.0 * f)
+        + 0.058793 * sin(2.0 * d - 2.0 * mp)
+        + 0.057066 * sin(2.0 * d - m - mp)
+        + 0.053322 * sin(2.0 * d + mp)
+        + 0.045758 * sin(2.0 * d - m)
+        - 0.040923 * sin(m - mp)
+        - 0.034720 * sin(d)
+        - 0.030383 * sin(m + mp);
+
+    let latitude = 5.128122 * sin(f)
+        + 0.280602 * sin(mp + f)
+        + 0.277693 * sin(mp - f)
+        + 0.173237 * sin(2.0 * d - f)
+        + 0.055413 * sin(2.0 * d - mp + f)
+        + 0.046271 * sin(2.0 * d - mp - f)
+        + 0.032573 * sin(2.0 * d + f)
+        + 0.017198 * sin(2.0 * mp + f);
+
+    let distance = 385000.56
+        - 20905.355 * cos(mp)
+        - 3699.111 * cos(2.0 * d - mp)
+        - 2955.968 * cos(2.0 * d)
+        - 569.925 * cos(2.0 * mp);
+
+    let obliquity = 23.439291 - 0.0130042 * t;
+
+    let right_ascension = (sin(longitude) * cos(obliquity)
+        - latitude.to_radians().tan() * sin(obliquity))
+    .atan2(cos(longitude))
+    .to_degrees();
+    let declination = (sin(latitude) * cos(obliquity)
+        + cos(latitude) * sin(obliquity) * sin(longitude))
+    .asin()
+    .to_degrees();
+
+    let phase_angle = 180.0 - d - 6.289 * sin(mp) + 2.100 * sin(m)
+        - 1.274 * sin(2.0 * d - mp)
+        - 0.658 * sin(2.0 * d)
+        - 0.214 * sin(2.0 * mp)
+        - 0.110 * sin(d);
+
+    LunarCoordinates {
+        right_ascension,
+        declination,
+        parallax: (EARTH_RADIUS_KM / distance).asin().to_degrees(),
+        elongation: d.rem_euclid(360.0),
+        phase_angle,
+    }
+}
+
+/// Altitude of the moon center seen from the surface, before refraction
+fn topocentric_altitude(
+    coords: &LunarCoordinates,
+    datetime: NaiveDateTime,
+    latitude: f64,
+    longitude: f64,
+) -> (f64, f64) {
+    let sidereal_time = solar::local_sidereal_time(datetime, longitude);
+    let (altitude, azimuth) = solar::horizontal(
+        coords.right_ascension,
+        coords.declination,
+        sidereal_time,
+        latitude,
+    );
+
+    (altitude - coords.parallax * cos(altitude), azimuth)
+}
+
+/// Calculates the position and the phase of the moon at a given instant and place
+///
+/// # Arguments
+///
+/// * `datetime` - The UTC instant at which to calculate the position
+/// * `latitude` - The latitude of the observer. Expressed as degrees
+/// * `longitude` - The longitude of the observer. Expressed as degrees, positive to the east
+pub fn position(datetime: NaiveDateTime, latitude: f64, longitude: f64) -> MoonPosition {
+    let coords = lunar_coordinates(datetime);
+    let (altitude, azimuth) = topocentric_altitude(&coords, datetime, latitude, longitude);
+
+    MoonPosition {
+        altitude: altitude + solar::refraction(altitude),
+        azimuth,
+        illuminated_fraction: (1.0 + cos(coords.phase_angle)) / 2.0,
+        phase: coords.elongation / 360.0,
+    }
+}
+
+/// Calculates the moonrise and moonset times on a given UTC date
+///
+/// The moon moves too fast for a closed form, so the altitude is sampled every ten minutes
+/// and each crossing of the horizon is refined by bisection.
+pub fn moon_times(date: NaiveDate, latitude: f64, longitude: f64) -> MoonEvents {
+    let altitude = |datetime: NaiveDateTime| {
+        let coords = lunar_coordinates(datetime);
+        topocentric_altitude(&coords, datetime, latitude, longitude).0 - MOONRISE
+    };
+
+    let step = Duration::minutes(10);
+    let mut events = MoonEvents {
+        rise: None,
+        set: None,
+    };
+
+    let mut start = date.and_hms_opt(0, 0, 0).unwrap();
+    let mut start_altitude = altitude(start);
+    for _ in 0..(24 * 6) {
+        let end = start + step;
+        let end_altitude = altitude(end);
+
+        if start_altitude.signum() != end_altitude.signum() {
+            let (mut low, mut high) = (start, end);
+            while high - low > Duration::seconds(1) {
+                let middle = low + Duration::seconds((high - low).num_seconds() / 2);
+                if altitude(middle).signum() == start_altitude.signum() {
+                    low = middle;
+                } else {
+                    high = middle;
+                }
+            }
+
+            if start_altitude < 0.0 {
+                events.rise.get_or_insert(high);
+            } else {
+                events.set.get_or_insert(high);
+            }
+        }
+
+        start = end;
+        start_altitude = end_altitude;
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    const PARIS_LAT: f64 = 48.864716;
+    const PARIS_LON: f64 = 2.349014;
+
+    #[test]
+    fn test_phase() {
+        // full moon on 2022-07-13 18:37 UTC
+        let d = Utc.with_ymd_and_hms(2022, 7, 13, 18, 37, 0).unwrap();
+        let moon = super::position(d.naive_utc(), PARIS_LAT, PARIS_LON);
+        assert!(moon.illuminated_fraction > 0.99, "{:?}", moon);
+        assert!((moon.phase - 0.5).abs() < 0.02, "{:?}", moon);
+
+        // new moon on 2022-07-28 17:54 UTC
+        let d = Utc.with_ymd_and_hms(2022, 7, 28, 17, 54, 0).unwrap();
+        let moon = super::position(d.naive_utc(), PARIS_LAT, PARIS_LON);
+        assert!(moon.illuminated_fraction < 0.01, "{:?}", moon);
+    }
+
+    #[test]
+    fn test_moon_times() {
+        // Paris, 2022-07-13: moonset 02:54 UTC, moonrise of the full moon 20:21 UTC
+        let date = NaiveDate::from_ymd_opt(2022, 7, 13).unwrap();
+        let events = super::moon_times(date, PARIS_LAT, PARIS_LON);
+
+        let rise = events.rise.unwrap();
+        let expected = date.and_hms_opt(20, 21, 0).unwrap();
+        assert!((rise - expected).num_minutes().abs() < 3, "{:?}", events);
+
+        let set = events.set.unwrap();
+        let expected = date.and_hms_opt(2, 54, 0).unwrap();
+        assert!((set - expected).num_minutes().abs() < 3, "{:?}", events);
+    }
+}
@@ -1,7 +1,45 @@
 use std::rc::Rc;
 use usvg::NodeExt;
 
-include!(concat!(env!("OUT_DIR"), "/const_gen.rs"));
+use crate::angle::{Angle, PaletteAngle};
+use crate::moon::MoonPosition;
+use crate::season::Season;
+use crate::sky::{Phase, SkyState};
+use crate::solar;
+use crate::theme::{Color, FogBank, LandPath, Layer, Palette, Star, SunPath, Theme};
+use crate::weather::{Particle, Precipitation, Puff};
+
+/// Size of the scene, before it is fitted to the image
+pub const SCENE_WIDTH: f64 = 1600.0;
+pub const SCENE_HEIGHT: f64 = 900.0;
+
+const PIVOT_X: f64 = 800.0;
+const PIVOT_Y: f64 = 530.0;
+
+/// Center of the sun as drawn in the themes, moved to where the sun stands in the sky
+const SUN_X: f64 = 800.0;
+const SUN_Y: f64 = 270.0;
+
+const MOON_RADIUS: f64 = 16.0;
+const MOON_COLOR: Color = Color {
+    r: 244,
+    g: 241,
+    b: 222,
+};
+
+const SNOW_COLOR: Color = Color {
+    r: 255,
+    g: 255,
+    b: 255,
+};
+
+/// Color the landscape takes under a full moon high in a dark sky
+const MOONLIGHT: Color = Color {
+    r: 126,
+    g: 150,
+    b: 204,
+};
+const MOONLIGHT_STRENGTH: f64 = 0.22;
 
 struct Interpolation {
     data: Vec<f64>,
@@ -30,6 +68,40 @@ impl Interpolation {
     }
 }
 
+/// Picks the color of a palette at a given position, blending its morning and evening colors
+fn sample(colors: &[Color], angle: PaletteAngle) -> Color {
+    let morning = sample_at(colors, angle.morning);
+    if angle.evening <= 0.0 {
+        return morning;
+    }
+    mix(
+        &morning,
+        &sample_at(colors, angle.evening_angle()),
+        angle.evening,
+    )
+}
+
+/// Picks the color of a palette at a given angle, blending the two closest entries
+fn sample_at(colors: &[Color], angle: Angle) -> Color {
+    let i = angle.rem_euclid(360.0) * colors.len() as f64 / 360.0;
+
+    let min_value = i.floor() as usize % colors.len();
+    let max_value = (min_value + 1) % colors.len();
+    let factor = i.fract();
+
+    mix(&colors[min_value], &colors[max_value], factor)
+}
+
+fn mix(from: &Color, to: &Color, factor: f64) -> Color {
+    let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * factor).round() as u8;
+
+    Color {
+        r: mix(from.r, to.r),
+        g: mix(from.g, to.g),
+        b: mix(from.b, to.b),
+    }
+}
+
 fn color(offset: f64, r: u8, g: u8, b: u8) -> usvg::Stop {
     usvg::Stop {
         offset: usvg::NormalizedValue::new(offset),
@@ -38,77 +110,122 @@ fn color(offset: f64, r: u8, g: u8, b: u8) -> usvg::Stop {
     }
 }
 
-fn to_svg_segment(seg: &PathSegment) -> usvg::PathSegment {
-    match *seg {
-        PathSegment::MoveTo { x, y } => usvg::PathSegment::MoveTo { x, y },
-        PathSegment::LineTo { x, y } => usvg::PathSegment::LineTo { x, y },
-        PathSegment::CurveTo {
-            x1,
-            y1,
-            x2,
-            y2,
-            x,
-            y,
-        } => usvg::PathSegment::CurveTo {
-            x1,
-            y1,
-            x2,
-            y2,
-            x,
-            y,
-        },
-        PathSegment::ClosePath => usvg::PathSegment::ClosePath,
+/// Intermediate stops added between two stops of the sky gradient blended in another color space
+const GRADIENT_STEPS: usize = 8;
+
+/// Stops of the sky gradient; the renderer blends them in sRGB, so the ones of a palette
+/// asking for another color space get intermediate stops blended in that space
+fn sky_stops(stops: &[(f64, Color)], palette: &Palette) -> Vec<usvg::Stop> {
+    let interpolation = match palette.interpolation {
+        Some(interpolation) => interpolation,
+        None => {
+            return stops
+                .iter()
+                .map(|(offset, c)| color(*offset, c.r, c.g, c.b))
+                .collect()
+        }
+    };
+
+    let mut blended = Vec::new();
+    for pair in stops.windows(2) {
+        let ((from_offset, from), (to_offset, to)) = (pair[0], pair[1]);
+        for step in 0..GRADIENT_STEPS {
+            let t = step as f64 / GRADIENT_STEPS as f64;
+            let c = interpolation.mix(&from, &to, t);
+            blended.push(color(
+                from_offset + (to_offset - from_offset) * t,
+                c.r,
+                c.g,
+                c.b,
+            ));
+        }
+    }
+    if let Some((offset, c)) = stops.last() {
+        blended.push(color(*offset, c.r, c.g, c.b));
     }
+
+    blended
+}
+
+/// How the local sky is laid over the scene
+///
+/// The scene faces south, with the zenith at the top of the rendered sky, margin included, and
+/// the horizon at the pivot. The east is drawn on the right when the scene is mirrored once
+/// rasterized, on the left otherwise, so that it always ends up on the left. Meridians are
+/// squeezed toward the zenith so that constellations keep their shape high in the sky.
+struct Projection {
+    /// Pixels per degree of sky
+    scale: f64,
+    /// 1 when the east is drawn on the right, -1 on the left
+    east: f64,
 }
 
-fn to_svg_transform(transform: &Transform) -> usvg::Transform {
-    usvg::Transform {
-        a: transform.a,
-        b: transform.b,
-        c: transform.c,
-        d: transform.d,
-        e: transform.e,
-        f: transform.f,
+impl Projection {
+    fn new(margins: Margins, mirror: bool) -> Self {
+        Self {
+            scale: (PIVOT_Y + margins.sky) / 90.0,
+            east: if mirror { 1.0 } else { -1.0 },
+        }
+    }
+
+    /// Places a point of the local sky in the scene, `None` when it is out of view
+    fn project(&self, altitude: f64, azimuth: f64) -> Option<(f64, f64)> {
+        if altitude < 0.0 {
+            return None;
+        }
+
+        let (x, y) = self.place(altitude, azimuth);
+        if (0.0..=SCENE_WIDTH).contains(&x) {
+            Some((x, y))
+        } else {
+            None
+        }
+    }
+
+    /// Places a point of the local sky in the scene, wherever it lands
+    fn place(&self, altitude: f64, azimuth: f64) -> (f64, f64) {
+        let offset = 180.0 - azimuth.rem_euclid(360.0);
+        let x = PIVOT_X + self.east * offset * altitude.to_radians().cos() * self.scale;
+        let y = PIVOT_Y - altitude * self.scale;
+        (x, y)
     }
 }
 
-fn convert_star(star: &[PathSegment; 6]) -> usvg::Path {
-    usvg::Path {
+fn convert_star(star: &Star, sky: &SkyState, projection: &Projection) -> Option<usvg::Path> {
+    let (altitude, azimuth) = solar::horizontal(
+        star.right_ascension,
+        star.declination,
+        sky.sidereal_time,
+        sky.latitude,
+    );
+    let visibility = sky.visibility(star.magnitude, altitude);
+    if visibility <= 0.0 {
+        return None;
+    }
+    let (x, y) = projection.project(altitude, azimuth)?;
+
+    // brighter stars are bigger and more opaque, the faintest stay visible as dim dots
+    let radius = (2.2 - 0.4 * star.magnitude).clamp(0.6, 2.8);
+    let mut fill = usvg::Fill::from_paint(usvg::Paint::Color(usvg::Color::new_rgb(255, 255, 255)));
+    let opacity = (1.0 - 0.15 * star.magnitude).clamp(0.35, 1.0);
+    fill.opacity = usvg::NormalizedValue::new(opacity * visibility);
+
+    Some(usvg::Path {
         id: String::default(),
         transform: usvg::Transform::default(),
         visibility: usvg::Visibility::Visible,
-        fill: Some(usvg::Fill::from_paint(usvg::Paint::Color(
-            usvg::Color::new_rgb(255, 255, 255),
-        ))),
+        fill: Some(fill),
         stroke: None,
         rendering_mode: usvg::ShapeRendering::GeometricPrecision,
         text_bbox: None,
-        data: Rc::new(usvg::PathData(star.iter().map(to_svg_segment).collect())),
-    }
+        data: Rc::new(circle(x, y, radius)),
+    })
 }
 
-fn sun_node(sun: &SunPath, angle: usize) -> usvg::Node {
-    let mut path = usvg::PathData(sun.d.iter().map(to_svg_segment).collect());
-
-    let angle = angle as f64;
-
-    let pivot_x = 800.0;
-    let pivot_y = 530.0;
-
-    let a = (-angle - 180.0).to_radians();
-    let a_cos = a.cos();
-    let a_sin = a.sin();
-    let e = -pivot_x * a_cos + pivot_y * a_sin + pivot_x;
-    let f = -pivot_x * a_sin - pivot_y * a_cos + pivot_y;
+fn sun_node(sun: &SunPath, (x, y): (f64, f64)) -> usvg::Node {
+    let mut path = sun.d.clone();
 
-    path.transform(usvg::Transform {
-        a: a_cos,
-        b: a_sin,
-        c: -a_sin,
-        d: a_cos,
-        e,
-        f,
-    });
+    path.transform(usvg::Transform::new_translate(x - SUN_X, y - SUN_Y));
 
     usvg::Node::new(usvg::NodeKind::Path(usvg::Path {
         id: String::default(),
@@ -124,7 +241,7 @@ fn sun_node(sun: &SunPath, angle: usize) -> usvg::Node {
     }))
 }
 
-fn convert_sun(sun: &SunPath, angle: usize) -> usvg::Node {
+fn convert_sun(sun: &SunPath, position: (f64, f64)) -> usvg::Node {
     match sun.opacity {
         Some(opacity) => {
             let mut group = usvg::Node::new(usvg::NodeKind::Group(usvg::Group {
@@ -139,41 +256,230 @@ fn convert_sun(sun: &SunPath, angle: usize) -> usvg::Node {
                 enable_background: None,
             }));
 
-            group.append(sun_node(sun, angle));
+            group.append(sun_node(sun, position));
 
             group
         }
-        None => sun_node(sun, angle),
-    }
-}
-
-fn convert_landscape(land: &LandPath, angle: usize) -> usvg::Path {
-    let id = land.id;
-    let mut split = id.split('-');
-    let pos = split.next().unwrap();
-
-    let colors: &[Color] = match pos {
-        "1" => COLORS_LAYER1,
-        "2" => COLORS_LAYER2,
-        "3" => COLORS_LAYER3,
-        "4" => COLORS_LAYER4,
-        "5" => COLORS_LAYER5,
-        "6" => COLORS_LAYER6,
-        "7" => COLORS_LAYER7,
-        "8" => COLORS_LAYER8,
-        "9" => COLORS_LAYER9,
-        "10" => COLORS_LAYER10,
-        "11" => COLORS_LAYER11,
-        _ => COLORS_LAYER11,
+        None => sun_node(sun, position),
+    }
+}
+
+fn ellipse(cx: f64, cy: f64, rx: f64, ry: f64) -> usvg::PathData {
+    const K: f64 = 0.5523;
+
+    usvg::PathData(vec![
+        usvg::PathSegment::MoveTo { x: cx, y: cy - ry },
+        usvg::PathSegment::CurveTo {
+            x1: cx + rx * K,
+            y1: cy - ry,
+            x2: cx + rx,
+            y2: cy - ry * K,
+            x: cx + rx,
+            y: cy,
+        },
+        usvg::PathSegment::CurveTo {
+            x1: cx + rx,
+            y1: cy + ry * K,
+            x2: cx + rx * K,
+            y2: cy + ry,
+            x: cx,
+            y: cy + ry,
+        },
+        usvg::PathSegment::CurveTo {
+            x1: cx - rx * K,
+            y1: cy + ry,
+            x2: cx - rx,
+            y2: cy + ry * K,
+            x: cx - rx,
+            y: cy,
+        },
+        usvg::PathSegment::CurveTo {
+            x1: cx - rx,
+            y1: cy - ry * K,
+            x2: cx - rx * K,
+            y2: cy - ry,
+            x: cx,
+            y: cy - ry,
+        },
+        usvg::PathSegment::ClosePath,
+    ])
+}
+
+fn circle(cx: f64, cy: f64, r: f64) -> usvg::PathData {
+    ellipse(cx, cy, r, r)
+}
+
+/// Lit part of the moon disc: half the disc on the lit side, closed by the terminator ellipse
+///
+/// `side` is `1.0` when the lit limb is on the right of the disc and `-1.0` on the left.
+fn moon_phase(cx: f64, cy: f64, r: f64, illuminated_fraction: f64, side: f64) -> usvg::PathData {
+    const K: f64 = 0.5523;
+
+    let limb = side * r;
+    // the terminator bulges toward the limb for a crescent and away from it when gibbous
+    let terminator = side * r * (1.0 - 2.0 * illuminated_fraction);
+
+    usvg::PathData(vec![
+        usvg::PathSegment::MoveTo { x: cx, y: cy - r },
+        usvg::PathSegment::CurveTo {
+            x1: cx + limb * K,
+            y1: cy - r,
+            x2: cx + limb,
+            y2: cy - r * K,
+            x: cx + limb,
+            y: cy,
+        },
+        usvg::PathSegment::CurveTo {
+            x1: cx + limb,
+            y1: cy + r * K,
+            x2: cx + limb * K,
+            y2: cy + r,
+            x: cx,
+            y: cy + r,
+        },
+        usvg::PathSegment::CurveTo {
+            x1: cx + terminator * K,
+            y1: cy + r,
+            x2: cx + terminator,
+            y2: cy + r * K,
+            x: cx + terminator,
+            y: cy,
+        },
+        usvg::PathSegment::CurveTo {
+            x1: cx + terminator,
+            y1: cy - r * K,
+            x2: cx + terminator * K,
+            y2: cy - r,
+            x: cx,
+            y: cy - r,
+        },
+        usvg::PathSegment::ClosePath,
+    ])
+}
+
+fn moon_path(data: usvg::PathData, opacity: f64) -> usvg::Node {
+    let mut fill = usvg::Fill::from_paint(usvg::Paint::Color(usvg::Color::new_rgb(
+        MOON_COLOR.r,
+        MOON_COLOR.g,
+        MOON_COLOR.b,
+    )));
+    fill.opacity = usvg::NormalizedValue::new(opacity);
+
+    usvg::Node::new(usvg::NodeKind::Path(usvg::Path {
+        id: String::default(),
+        transform: usvg::Transform::default(),
+        visibility: usvg::Visibility::Visible,
+        fill: Some(fill),
+        stroke: None,
+        rendering_mode: usvg::ShapeRendering::GeometricPrecision,
+        text_bbox: None,
+        data: Rc::new(data),
+    }))
+}
+
+fn convert_moon(
+    moon: &MoonPosition,
+    sky: &SkyState,
+    projection: &Projection,
+) -> Option<usvg::Node> {
+    // the moon is placed among the stars
+    let (cx, cy) = projection.project(moon.altitude, moon.azimuth)?;
+
+    // a waxing moon is lit from the west, the side opposite to the east
+    let side = if moon.is_waxing() {
+        -projection.east
+    } else {
+        projection.east
     };
 
-    let c = &colors[angle];
+    let opacity = if sky.phase == Phase::Day { 0.5 } else { 1.0 };
+    let mut group = usvg::Node::new(usvg::NodeKind::Group(usvg::Group {
+        id: String::default(),
+        transform: usvg::Transform::default(),
+        opacity: usvg::NormalizedValue::new(opacity),
+        clip_path: None,
+        mask: None,
+        filter: Vec::new(),
+        filter_fill: None,
+        filter_stroke: None,
+        enable_background: None,
+    }));
+
+    let glow = 0.12 * moon.illuminated_fraction;
+    group.append(moon_path(circle(cx, cy, MOON_RADIUS * 3.0), glow));
+    group.append(moon_path(circle(cx, cy, MOON_RADIUS * 1.6), glow));
+    // earthshine on the dark part of the disc
+    group.append(moon_path(circle(cx, cy, MOON_RADIUS), 0.12));
+    group.append(moon_path(
+        moon_phase(cx, cy, MOON_RADIUS, moon.illuminated_fraction, side),
+        1.0,
+    ));
+
+    Some(group)
+}
 
-    let path = usvg::PathData(land.d.iter().map(to_svg_segment).collect());
+/// How much the landscape is lit by the moon, from 0 to 1
+fn moonlight(sky: &SkyState) -> f64 {
+    let moon = match &sky.moon {
+        Some(moon) => moon,
+        None => return 0.0,
+    };
 
+    let height = (moon.altitude / 10.0).clamp(0.0, 1.0);
+    // moonlight only shows once the sky is dark enough
+    let darkness = ((-sky.elevation_deg - 6.0) / 6.0).clamp(0.0, 1.0);
+
+    moon.illuminated_fraction * height * darkness
+}
+
+/// Relative luminance of a color, from 0 to 255
+fn luminance(color: &Color) -> f64 {
+    0.2126 * color.r as f64 + 0.7152 * color.g as f64 + 0.0722 * color.b as f64
+}
+
+/// Moves a color toward the grey of the same luminance
+fn desaturate(color: &Color, amount: f64) -> Color {
+    let l = luminance(color).round() as u8;
+
+    mix(color, &Color { r: l, g: l, b: l }, amount)
+}
+
+/// Blends the seasonal tints of a layer over its color, keeping its brightness
+fn seasonal(color: &Color, layer: &Layer, season: &Season) -> Color {
+    let weighted = [
+        (&layer.winter, season.winter),
+        (&layer.spring, season.spring),
+        (&layer.summer, season.summer),
+        (&layer.autumn, season.autumn),
+    ];
+
+    let (mut r, mut g, mut b) = (0.0, 0.0, 0.0);
+    for (tint, weight) in weighted {
+        // the tint takes the luminance of the color, so it does not light up the night
+        let scale = luminance(color) / luminance(&tint.color).max(1.0);
+        let tinted = Color {
+            r: (tint.color.r as f64 * scale).min(255.0) as u8,
+            g: (tint.color.g as f64 * scale).min(255.0) as u8,
+            b: (tint.color.b as f64 * scale).min(255.0) as u8,
+        };
+        let c = mix(color, &tinted, tint.strength);
+
+        r += c.r as f64 * weight;
+        g += c.g as f64 * weight;
+        b += c.b as f64 * weight;
+    }
+
+    Color {
+        r: r.round() as u8,
+        g: g.round() as u8,
+        b: b.round() as u8,
+    }
+}
+
+fn landscape_path(land: &LandPath, transform: usvg::Transform, c: &Color) -> usvg::Path {
     usvg::Path {
         id: String::default(),
-        transform: to_svg_transform(&land.transform),
+        transform,
         visibility: usvg::Visibility::Visible,
         fill: Some(usvg::Fill::from_paint(usvg::Paint::Color(
             usvg::Color::new_rgb(c.r, c.g, c.b),
@@ -181,15 +487,275 @@ fn convert_landscape(land: &LandPath, angle: usize) -> usvg::Path {
         stroke: None,
         rendering_mode: usvg::ShapeRendering::GeometricPrecision,
         text_bbox: None,
-        data: Rc::new(path),
+        data: Rc::new(land.d.clone()),
+    }
+}
+
+fn convert_landscape(
+    land: &LandPath,
+    layer: &Layer,
+    angle: PaletteAngle,
+    moonlight: f64,
+    season: &Season,
+) -> usvg::Path {
+    let c = mix(
+        &seasonal(&sample(&layer.colors, angle), layer, season),
+        &MOONLIGHT,
+        moonlight * MOONLIGHT_STRENGTH,
+    );
+
+    landscape_path(land, land.transform, &c)
+}
+
+/// Snow lying on top of a layer: the layer shape, raised a bit and drawn right under it
+fn convert_snow(
+    land: &LandPath,
+    palette: &Palette,
+    angle: PaletteAngle,
+    moonlight: f64,
+    season: &Season,
+) -> Option<usvg::Node> {
+    // only in the depth of winter
+    let amount = ((season.winter - 0.5) * 2.0).clamp(0.0, 1.0);
+    if amount <= 0.0 || !palette.snow_layers.contains(&land.layer) {
+        return None;
+    }
+
+    // snow is lit like the farthest mountains, which take the color of the sky, and only
+    // stands out from them in daylight
+    let lit = sample(&palette.layers[0].colors, angle);
+    let c = mix(&lit, &SNOW_COLOR, 0.6 * luminance(&lit) / 255.0);
+    let c = mix(&c, &MOONLIGHT, moonlight * MOONLIGHT_STRENGTH);
+
+    let mut transform = land.transform;
+    transform.f -= palette.snow_depth;
+
+    let mut group = opacity_group(amount);
+    group.append_kind(usvg::NodeKind::Path(landscape_path(land, transform, &c)));
+
+    Some(group)
+}
+
+fn opacity_group(opacity: f64) -> usvg::Node {
+    usvg::Node::new(usvg::NodeKind::Group(usvg::Group {
+        id: String::default(),
+        transform: usvg::Transform::default(),
+        opacity: usvg::NormalizedValue::new(opacity),
+        clip_path: None,
+        mask: None,
+        filter: Vec::new(),
+        filter_fill: None,
+        filter_stroke: None,
+        enable_background: None,
+    }))
+}
+
+fn fill_path(data: usvg::PathData, c: &Color, opacity: f64) -> usvg::Path {
+    let mut fill = usvg::Fill::from_paint(usvg::Paint::Color(usvg::Color::new_rgb(c.r, c.g, c.b)));
+    fill.opacity = usvg::NormalizedValue::new(opacity);
+
+    usvg::Path {
+        id: String::default(),
+        transform: usvg::Transform::default(),
+        visibility: usvg::Visibility::Visible,
+        fill: Some(fill),
+        stroke: None,
+        rendering_mode: usvg::ShapeRendering::GeometricPrecision,
+        text_bbox: None,
+        data: Rc::new(data),
+    }
+}
+
+/// Colors of the lit and the shaded side of the clouds, taken from the sky around them
+fn cloud_colors(palette: &Palette, angle: PaletteAngle) -> (Color, Color) {
+    let light = mix(
+        &sample(&palette.sky_horizon, angle),
+        &sample(&palette.sky_mid, angle),
+        0.3,
+    );
+    // the second layer is the darker one against the sky, when there is one
+    let layer = palette.layers.get(1).unwrap_or(&palette.layers[0]);
+    let shadow = mix(&sample(&layer.colors, angle), &light, 0.35);
+
+    (light, shadow)
+}
+
+fn convert_cloud(
+    puffs: &[Puff],
+    palette: &Palette,
+    angle: PaletteAngle,
+    (sun_x, sun_y): (f64, f64),
+) -> usvg::Node {
+    let (light, shadow) = cloud_colors(palette, angle);
+
+    let mut group = opacity_group(0.9);
+    for puff in puffs {
+        group.append_kind(usvg::NodeKind::Path(fill_path(
+            ellipse(puff.x, puff.y, puff.rx, puff.ry),
+            &shadow,
+            1.0,
+        )));
     }
+
+    // the side facing the sun is lit
+    for puff in puffs {
+        let (dx, dy) = (sun_x - puff.x, sun_y - puff.y);
+        let distance = dx.hypot(dy).max(1.0);
+        let offset = 0.25 * puff.ry;
+
+        group.append_kind(usvg::NodeKind::Path(fill_path(
+            ellipse(
+                puff.x + dx / distance * offset,
+                puff.y + dy / distance * offset,
+                puff.rx * 0.8,
+                puff.ry * 0.8,
+            ),
+            &light,
+            0.8,
+        )));
+    }
+
+    group
 }
 
-pub fn render(angle: usize) -> usvg::Tree {
+fn fog_gradient(
+    bank: &FogBank,
+    palette: &Palette,
+    angle: PaletteAngle,
+    density: f64,
+) -> usvg::LinearGradient {
+    let c = mix(
+        &sample(&palette.sky_horizon, angle),
+        &sample(&palette.layers[0].colors, angle),
+        0.5,
+    );
+    let stop = |offset: f64, opacity: f64| usvg::Stop {
+        offset: usvg::NormalizedValue::new(offset),
+        color: usvg::Color::new_rgb(c.r, c.g, c.b),
+        opacity: usvg::NormalizedValue::new(opacity),
+    };
+
+    usvg::LinearGradient {
+        id: format!("fog-{}", bank.layer),
+        x1: 0.0,
+        y1: bank.top,
+        x2: 0.0,
+        y2: bank.bottom,
+        base: usvg::BaseGradient {
+            units: usvg::Units::UserSpaceOnUse,
+            transform: usvg::Transform::default(),
+            spread_method: usvg::SpreadMethod::Pad,
+            stops: vec![stop(0.0, 0.0), stop(0.7, density), stop(1.0, density * 0.5)],
+        },
+    }
+}
+
+fn convert_fog(bank: &FogBank) -> usvg::Path {
+    usvg::Path {
+        id: String::default(),
+        transform: usvg::Transform::default(),
+        visibility: usvg::Visibility::Visible,
+        fill: Some(usvg::Fill::from_paint(usvg::Paint::Link(format!(
+            "fog-{}",
+            bank.layer
+        )))),
+        stroke: None,
+        rendering_mode: usvg::ShapeRendering::GeometricPrecision,
+        text_bbox: None,
+        data: Rc::new(usvg::PathData::from_rect(
+            usvg::Rect::new(0.0, bank.top, 1600.0, bank.bottom - bank.top).unwrap(),
+        )),
+    }
+}
+
+fn convert_particle(
+    particle: &Particle,
+    precipitation: Precipitation,
+    palette: &Palette,
+    angle: PaletteAngle,
+) -> usvg::Path {
+    let (light, _) = cloud_colors(palette, angle);
+    let c = mix(&light, &SNOW_COLOR, 0.4);
+
+    if precipitation == Precipitation::Snow {
+        return fill_path(
+            circle(particle.x, particle.y, particle.size),
+            &c,
+            particle.opacity,
+        );
+    }
+
+    // raindrops fall as slightly slanted streaks
+    let data = usvg::PathData(vec![
+        usvg::PathSegment::MoveTo {
+            x: particle.x,
+            y: particle.y,
+        },
+        usvg::PathSegment::LineTo {
+            x: particle.x + particle.size * 0.2,
+            y: particle.y + particle.size,
+        },
+    ]);
+
+    usvg::Path {
+        id: String::default(),
+        transform: usvg::Transform::default(),
+        visibility: usvg::Visibility::Visible,
+        fill: None,
+        stroke: Some(usvg::Stroke {
+            paint: usvg::Paint::Color(usvg::Color::new_rgb(c.r, c.g, c.b)),
+            width: usvg::StrokeWidth::new(1.2),
+            opacity: usvg::NormalizedValue::new(particle.opacity * 0.6),
+            linecap: usvg::LineCap::Round,
+            ..usvg::Stroke::default()
+        }),
+        rendering_mode: usvg::ShapeRendering::GeometricPrecision,
+        text_bbox: None,
+        data: Rc::new(data),
+    }
+}
+
+/// Room rendered around the scene, expressed as scene pixels
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Margins {
+    /// Sky added over the scene
+    pub sky: f64,
+    /// Ground added under the scene
+    pub ground: f64,
+}
+
+/// Renders the scene for a palette angle in whole degrees
+pub fn render_angle(angle: usize, theme: &Theme) -> usvg::Tree {
+    render(&SkyState::from_angle(angle as Angle), theme)
+}
+
+/// Renders the scene to be mirrored when rasterized, as the default camera does
+pub fn render(sky: &SkyState, theme: &Theme) -> usvg::Tree {
+    render_extended(sky, theme, Margins::default(), true)
+}
+
+/// Renders the scene with more sky over it and more ground under it, the view box of the tree
+/// starting above 0 to keep the coordinates of the scene
+///
+/// The sky is drawn the other way round when the scene is to be mirrored, `mirror`, so that only
+/// the landscape ends up flipped.
+pub fn render_extended(
+    sky: &SkyState,
+    theme: &Theme,
+    margins: Margins,
+    mirror: bool,
+) -> usvg::Tree {
+    let angle = sky.palette_angle();
+    let projection = Projection::new(margins, mirror);
+    let sun_position = projection.place(sky.elevation_deg, sky.azimuth_deg);
+    let palette = &theme.palette;
+
+    let top = -margins.sky;
+    let height = SCENE_HEIGHT + margins.sky + margins.ground;
     let svg = usvg::Svg {
-        size: usvg::Size::new(1600.0, 900.0).unwrap(),
+        size: usvg::Size::new(SCENE_WIDTH, height).unwrap(),
         view_box: usvg::ViewBox {
-            rect: usvg::Rect::new(0.0, 0.0, 1600.0, 900.0).unwrap(),
+            rect: usvg::Rect::new(0.0, top, SCENE_WIDTH, height).unwrap(),
             aspect: usvg::AspectRatio::default(),
         },
     };
@@ -197,9 +763,10 @@ pub fn render(angle: usize) -> usvg::Tree {
 
     // defs
 
-    let c1 = &SKY_ZENITH[angle];
-    let c2 = &SKY_MID[angle];
-    let c3 = &SKY_HORIZON[angle];
+    let desaturation = sky.weather.desaturation;
+    let c1 = desaturate(&sample(&palette.sky_zenith, angle), desaturation);
+    let c2 = desaturate(&sample(&palette.sky_mid, angle), desaturation);
+    let c3 = desaturate(&sample(&palette.sky_horizon, angle), desaturation);
 
     let inter1 = Interpolation::new(vec![
         0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.12, 0.21,
@@ -214,11 +781,14 @@ pub fn render(angle: usize) -> usvg::Tree {
         units: usvg::Units::UserSpaceOnUse,
         transform: usvg::Transform::default(),
         spread_method: usvg::SpreadMethod::Pad,
-        stops: vec![
-            color(inter1.interpolate(angle as f64, 360.0), c1.r, c1.g, c1.b),
-            color(inter2.interpolate(angle as f64, 360.0), c2.r, c2.g, c2.b),
-            color(1.0, c3.r, c3.g, c3.b),
-        ],
+        stops: sky_stops(
+            &[
+                (angle.blend(|a| inter1.interpolate(a, 360.0)), c1),
+                (angle.blend(|a| inter2.interpolate(a, 360.0)), c2),
+                (1.0, c3),
+            ],
+            palette,
+        ),
     };
 
     let sky_gradient = usvg::LinearGradient {
@@ -270,11 +840,19 @@ pub fn render(angle: usize) -> usvg::Tree {
     };
     tree.append_to_defs(usvg::NodeKind::RadialGradient(vignette_gradient));
 
+    let fog = sky.weather.fog;
+    if fog > 0.0 {
+        for bank in &theme.fog {
+            let gradient = fog_gradient(bank, palette, angle, fog);
+            tree.append_to_defs(usvg::NodeKind::LinearGradient(gradient));
+        }
+    }
+
     // nodes
 
     let mut root = tree.root();
 
-    let sky = usvg::Path {
+    let sky_path = usvg::Path {
         id: String::default(),
         transform: usvg::Transform::default(),
         visibility: usvg::Visibility::Visible,
@@ -283,10 +861,10 @@ pub fn render(angle: usize) -> usvg::Tree {
         rendering_mode: usvg::ShapeRendering::GeometricPrecision,
         text_bbox: None,
         data: Rc::new(usvg::PathData::from_rect(
-            usvg::Rect::new(0.0, 0.0, 1600.0, 540.0).unwrap(),
+            usvg::Rect::new(0.0, top, 1600.0, 540.0 - top).unwrap(),
         )),
     };
-    root.append_kind(usvg::NodeKind::Path(sky));
+    root.append_kind(usvg::NodeKind::Path(sky_path));
 
     let reflection = usvg::Path {
         id: String::default(),
@@ -302,43 +880,111 @@ pub fn render(angle: usize) -> usvg::Tree {
     };
     root.append_kind(usvg::NodeKind::Path(reflection));
 
-    if angle <= 95 || angle >= 270 {
-        for star in STARS {
-            let elem = convert_star(star);
+    for star in &theme.stars {
+        if let Some(elem) = convert_star(star, sky, &projection) {
             root.append_kind(usvg::NodeKind::Path(elem));
         }
     }
 
+    if let Some(moon) = sky
+        .moon
+        .as_ref()
+        .and_then(|moon| convert_moon(moon, sky, &projection))
+    {
+        root.append(moon);
+    }
+
     // sun
-    if angle > 95 && angle < 270 {
-        for sun in SUN {
-            let elem = convert_sun(sun, angle);
-            root.append(elem);
+    let sun_visibility = sky.sun_visibility();
+    if sun_visibility > 0.0 {
+        let mut group = opacity_group(sun_visibility);
+
+        for sun in &theme.sun {
+            group.append(convert_sun(sun, sun_position));
         }
+        root.append(group);
     }
 
-    for land in LANDSCAPE {
-        let elem = convert_landscape(land, angle);
-        root.append_kind(usvg::NodeKind::Path(elem));
+    // clouds veil what is behind them before drawing their own shapes
+    let cover = sky.weather.cloud_cover;
+    if cover > 0.0 {
+        let (light, shadow) = cloud_colors(palette, angle);
+        root.append_kind(usvg::NodeKind::Path(fill_path(
+            usvg::PathData::from_rect(usvg::Rect::new(0.0, top, 1600.0, 540.0 - top).unwrap()),
+            &mix(&shadow, &light, 0.4),
+            cover * cover * 0.8,
+        )));
+
+        for cloud in sky.weather.clouds(sky.seed) {
+            root.append(convert_cloud(&cloud, palette, angle, sun_position));
+        }
     }
 
-    let bottom_color = &COLORS_LAYER11[angle];
+    let moonlight = moonlight(sky) * (1.0 - cover);
+    for (i, land) in theme.landscape.iter().enumerate() {
+        if let Some(snow) = convert_snow(land, palette, angle, moonlight, &sky.season) {
+            root.append(snow);
+        }
 
-    let bottom = usvg::Path {
-        id: String::default(),
-        transform: usvg::Transform::default(),
-        visibility: usvg::Visibility::Visible,
-        fill: Some(usvg::Fill::from_paint(usvg::Paint::Color(
-            usvg::Color::new_rgb(bottom_color.r, bottom_color.g, bottom_color.b),
-        ))),
-        stroke: None,
-        rendering_mode: usvg::ShapeRendering::GeometricPrecision,
-        text_bbox: None,
-        data: Rc::new(usvg::PathData::from_rect(
-            usvg::Rect::new(0.0, 714.0, 1600.0, 186.0).unwrap(),
-        )),
-    };
-    root.append_kind(usvg::NodeKind::Path(bottom));
+        // layers are checked when the theme is loaded
+        if let Some(layer) = palette.layer(&land.layer) {
+            let elem = convert_landscape(land, layer, angle, moonlight, &sky.season);
+            root.append_kind(usvg::NodeKind::Path(elem));
+        }
+
+        // fog lies over the last path of a layer
+        let next = theme.landscape.get(i + 1).map(|l| &l.layer);
+        if fog > 0.0 && next != Some(&land.layer) {
+            for bank in theme.fog.iter().filter(|bank| bank.layer == land.layer) {
+                root.append_kind(usvg::NodeKind::Path(convert_fog(bank)));
+            }
+        }
+    }
+
+    // the ground under the closest layer, running on under the scene
+    let last = &palette.layers[palette.layers.len() - 1];
+    let bottom_color = mix(
+        &seasonal(&sample(&last.colors, angle), last, &sky.season),
+        &MOONLIGHT,
+        moonlight * MOONLIGHT_STRENGTH,
+    );
+    let ground = theme
+        .ground
+        .or(Some(900.0).filter(|_| margins.ground > 0.0));
+    if let Some(ground) = ground {
+        let bottom = usvg::Path {
+            id: String::default(),
+            transform: usvg::Transform::default(),
+            visibility: usvg::Visibility::Visible,
+            fill: Some(usvg::Fill::from_paint(usvg::Paint::Color(
+                usvg::Color::new_rgb(bottom_color.r, bottom_color.g, bottom_color.b),
+            ))),
+            stroke: None,
+            rendering_mode: usvg::ShapeRendering::GeometricPrecision,
+            text_bbox: None,
+            data: Rc::new(usvg::PathData::from_rect(
+                usvg::Rect::new(0.0, ground, 1600.0, 900.0 + margins.ground - ground).unwrap(),
+            )),
+        };
+        root.append_kind(usvg::NodeKind::Path(bottom));
+    }
+
+    if let Some(precipitation) = sky.weather.precipitation {
+        // the particles of the scene are repeated over the margins
+        let first = -(margins.sky / 900.0).ceil() as i32;
+        let last = (margins.ground / 900.0).ceil() as i32;
+        for particle in sky.weather.particles(sky.seed) {
+            for tile in first..=last {
+                let y = particle.y + tile as f64 * 900.0;
+                if tile != 0 && !(top..=900.0 + margins.ground).contains(&y) {
+                    continue;
+                }
+                let particle = Particle { y, ..particle };
+                let elem = convert_particle(&particle, precipitation, palette, angle);
+                root.append_kind(usvg::NodeKind::Path(elem));
+            }
+        }
+    }
 
     let vignette = usvg::Path {
         id: String::default(),
@@ -349,7 +995,7 @@ pub fn render(angle: usize) -> usvg::Tree {
         rendering_mode: usvg::ShapeRendering::GeometricPrecision,
         text_bbox: None,
         data: Rc::new(usvg::PathData::from_rect(
-            usvg::Rect::new(0.0, 0.0, 1600.0, 900.0).unwrap(),
+            usvg::Rect::new(0.0, top, 1600.0, height).unwrap(),
         )),
     };
     root.append_kind(usvg::NodeKind::Path(vignette));
@@ -359,3 +1005,161 @@ pub fn render(angle: usize) -> usvg::Tree {
 
     tree
 }
+
+/// Copies the scene into an image of `width` by `height` pixels, placed by `transform` the way
+/// it is rasterized
+pub fn fit_tree(
+    tree: &usvg::Tree,
+    width: u32,
+    height: u32,
+    transform: usvg::Transform,
+) -> usvg::Tree {
+    // the view box of the scene is replaced by the one of the image
+    let view_box = tree.svg_node().view_box.rect;
+    let mut transform = transform;
+    transform.translate(-view_box.x(), -view_box.y());
+
+    let (width, height) = (width as f64, height as f64);
+    let image = usvg::Tree::create(usvg::Svg {
+        size: usvg::Size::new(width, height).unwrap(),
+        view_box: usvg::ViewBox {
+            rect: usvg::Rect::new(0.0, 0.0, width, height).unwrap(),
+            aspect: usvg::AspectRatio::default(),
+        },
+    });
+
+    let mut scene = usvg::Node::new(usvg::NodeKind::Group(usvg::Group {
+        id: "scene".to_string(),
+        transform,
+        opacity: usvg::NormalizedValue::new(1.0),
+        clip_path: None,
+        mask: None,
+        filter: Vec::new(),
+        filter_fill: None,
+        filter_stroke: None,
+        enable_background: None,
+    }));
+
+    // the scene is copied, so it can be placed once per image
+    let mut defs = image.defs();
+    for mut node in tree.defs().children() {
+        defs.append(node.make_deep_copy());
+    }
+    for mut node in tree.root().children().skip(1) {
+        scene.append(node.make_deep_copy());
+    }
+    image.root().append(scene);
+
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate};
+
+    use super::Margins;
+    use crate::sky::SkyState;
+    use crate::theme::{Color, Theme};
+
+    const PARIS_LAT: f64 = 48.864716;
+    const PARIS_LON: f64 = 2.349014;
+
+    #[test]
+    fn test_render_angle() {
+        let theme = Theme::embedded();
+        let options = usvg::XmlOptions::default();
+
+        let tree = super::render_angle(95, &theme);
+        let sky = SkyState::from_angle(95.0);
+        assert_eq!(
+            tree.to_string(&options),
+            super::render(&sky, &theme).to_string(&options)
+        );
+
+        // whole degrees past a full turn go round the palettes
+        let tree = super::render_angle(455, &theme);
+        assert_eq!(
+            tree.to_string(&options),
+            super::render(&sky, &theme).to_string(&options)
+        );
+    }
+
+    #[test]
+    fn test_projection() {
+        // the horizon stays at the pivot, the zenith goes to the top of the sky rendered
+        let projection = super::Projection::new(Margins::default(), true);
+        assert_eq!(projection.project(90.0, 123.0), Some((800.0, 0.0)));
+        assert_eq!(projection.project(0.0, 180.0), Some((800.0, 530.0)));
+        assert_eq!(projection.project(-1.0, 180.0), None);
+        assert_eq!(projection.project(10.0, 0.0), None);
+
+        let margins = Margins {
+            sky: 500.0,
+            ground: 200.0,
+        };
+        let projection = super::Projection::new(margins, true);
+        assert_eq!(projection.project(90.0, 123.0), Some((800.0, -500.0)));
+        assert_eq!(projection.project(0.0, 180.0), Some((800.0, 530.0)));
+
+        // the east is drawn on the right to be mirrored, or else right away on the left
+        let (east, y) = projection.place(10.0, 90.0);
+        let (west, _) = projection.place(10.0, 270.0);
+        assert!(east > 800.0 && west < 800.0);
+        let projection = super::Projection::new(margins, false);
+        let (x, mirrored_y) = projection.place(10.0, 90.0);
+        assert!(
+            (x - (1600.0 - east)).abs() < 1e-9 && mirrored_y == y,
+            "{}",
+            x
+        );
+    }
+
+    #[test]
+    fn test_culmination() {
+        let theme = Theme::embedded();
+        let palette = &theme.palette;
+        let projection = super::Projection::new(Margins::default(), true);
+
+        // through the solar noon in winter and the midnight of the summer solstice
+        let starts = [
+            NaiveDate::from_ymd_opt(2022, 1, 1)
+                .unwrap()
+                .and_hms_opt(11, 0, 0),
+            NaiveDate::from_ymd_opt(2022, 6, 21)
+                .unwrap()
+                .and_hms_opt(23, 0, 0),
+        ];
+        for start in starts.map(Option::unwrap) {
+            let mut previous: Option<([Color; 4], (f64, f64))> = None;
+            for minute in 0..120 {
+                let datetime = start + Duration::minutes(minute);
+                let sky = SkyState::at(datetime, PARIS_LAT, PARIS_LON, 0.0);
+                let angle = sky.palette_angle();
+                let colors = [
+                    super::sample(&palette.sky_zenith, angle),
+                    super::sample(&palette.sky_mid, angle),
+                    super::sample(&palette.sky_horizon, angle),
+                    super::sample(&palette.layers[0].colors, angle),
+                ];
+                let sun = projection.place(sky.elevation_deg, sky.azimuth_deg);
+
+                if let Some((previous_colors, (x, y))) = previous {
+                    for (c, p) in colors.iter().zip(&previous_colors) {
+                        let step = [c.r.abs_diff(p.r), c.g.abs_diff(p.g), c.b.abs_diff(p.b)];
+                        assert!(step.iter().all(|&s| s <= 4), "{} {:?}", datetime, step);
+                    }
+                    // behind the scene at the lower culmination, the sun goes round from one
+                    // side to the other
+                    let step = (sun.0 - x).hypot(sun.1 - y);
+                    assert!(
+                        step < 10.0 || sky.elevation_deg < 0.0,
+                        "{} {}",
+                        datetime,
+                        step
+                    );
+                }
+                previous = Some((colors, sun));
+            }
+        }
+    }
+}
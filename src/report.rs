@@ -0,0 +1,402 @@
+use serde::Deserialize;
+use std::fmt;
+use std::path::Path;
+
+use crate::weather::{Conditions, Precipitation};
+
+const METERS_IN_A_MILE: f64 = 1609.344;
+
+/// Temperature at or below which mixed precipitation falls as snow. Expressed as degrees Celsius
+const SNOW_TEMPERATURE: f64 = 1.0;
+
+#[derive(Debug)]
+pub enum ReportError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Metar(String),
+}
+
+impl fmt::Display for ReportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReportError::Io(e) => write!(f, "{}", e),
+            ReportError::Json(e) => write!(f, "invalid JSON report: {}", e),
+            ReportError::Metar(e) => write!(f, "invalid METAR report: {}", e),
+        }
+    }
+}
+
+impl From<std::io::Error> for ReportError {
+    fn from(e: std::io::Error) -> Self {
+        ReportError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ReportError {
+    fn from(e: serde_json::Error) -> Self {
+        ReportError::Json(e)
+    }
+}
+
+/// Kind of precipitation as reported, before the temperature settles mixed ones
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Falling {
+    Drizzle,
+    Rain,
+    Snow,
+    Mixed,
+}
+
+/// Weather as observed, in the units of the reports
+#[derive(Debug, Default)]
+struct Observation {
+    /// Fraction of the sky covered by clouds
+    cloud_cover: Option<f64>,
+    /// Horizontal visibility, in meters
+    visibility: Option<f64>,
+    /// Fog or haze reported as such, from 0 to 1
+    obscuration: f64,
+    falling: Option<(Falling, f64)>,
+    /// Air temperature, in degrees Celsius
+    temperature: Option<f64>,
+}
+
+impl Observation {
+    fn conditions(&self) -> Conditions {
+        let cloud_cover = self.cloud_cover.unwrap_or(0.0).clamp(0.0, 1.0);
+
+        let haze: f64 = match self.visibility {
+            Some(v) if v < 1000.0 => 0.9,
+            Some(v) if v < 5000.0 => 0.45,
+            Some(v) if v < 8000.0 => 0.2,
+            _ => 0.0,
+        };
+        let fog = haze.max(self.obscuration);
+
+        let (precipitation, intensity) = match self.falling {
+            Some((falling, intensity)) => {
+                let cold = self.temperature.unwrap_or(10.0) <= SNOW_TEMPERATURE;
+                let (kind, intensity) = match falling {
+                    Falling::Drizzle => (Precipitation::Rain, intensity * 0.5),
+                    Falling::Rain => (Precipitation::Rain, intensity),
+                    Falling::Snow => (Precipitation::Snow, intensity),
+                    Falling::Mixed if cold => (Precipitation::Snow, intensity),
+                    Falling::Mixed => (Precipitation::Rain, intensity),
+                };
+                (Some(kind), intensity.clamp(0.0, 1.0))
+            }
+            None => (None, 0.0),
+        };
+
+        // a grey sky washes the colors out, rain and fog even more
+        let desaturation = (0.45 * cloud_cover + 0.3 * intensity + 0.3 * fog).min(1.0);
+
+        Conditions {
+            cloud_cover,
+            fog,
+            precipitation,
+            intensity,
+            desaturation,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum JsonPrecipitation {
+    None,
+    Drizzle,
+    Rain,
+    Snow,
+    Sleet,
+}
+
+/// Weather report written as JSON, every field is optional
+#[derive(Debug, Deserialize)]
+struct JsonReport {
+    /// Fraction of the sky covered by clouds, from 0 to 1
+    cloud_cover: Option<f64>,
+    precipitation: Option<JsonPrecipitation>,
+    /// How hard it rains or snows, from 0 to 1
+    intensity: Option<f64>,
+    /// Horizontal visibility, in meters
+    visibility: Option<f64>,
+    /// Air temperature, in degrees Celsius
+    temperature: Option<f64>,
+}
+
+/// Reads a weather report from a JSON object
+///
+/// ```json
+/// {"cloud_cover": 0.8, "precipitation": "rain", "intensity": 0.3, "visibility": 6000, "temperature": 7.5}
+/// ```
+pub fn parse_json(text: &str) -> Result<Conditions, ReportError> {
+    let report: JsonReport = serde_json::from_str(text)?;
+
+    let falling = match report.precipitation {
+        None | Some(JsonPrecipitation::None) => None,
+        Some(JsonPrecipitation::Drizzle) => Some(Falling::Drizzle),
+        Some(JsonPrecipitation::Rain) => Some(Falling::Rain),
+        Some(JsonPrecipitation::Snow) => Some(Falling::Snow),
+        Some(JsonPrecipitation::Sleet) => Some(Falling::Mixed),
+    };
+
+    let observation = Observation {
+        cloud_cover: report.cloud_cover,
+        visibility: report.visibility,
+        obscuration: 0.0,
+        falling: falling.map(|f| (f, report.intensity.unwrap_or(0.5))),
+        temperature: report.temperature,
+    };
+    Ok(observation.conditions())
+}
+
+/// Parses a visibility group, either in meters (`0800`, `9999`) or in statute miles (`1/2SM`)
+fn metar_visibility(token: &str) -> Option<f64> {
+    if let Some(miles) = token.strip_suffix("SM") {
+        let miles = miles.trim_start_matches(['P', 'M']);
+        let value = match miles.split_once('/') {
+            Some((n, d)) => n.parse::<f64>().ok()? / d.parse::<f64>().ok()?,
+            None => miles.parse::<f64>().ok()?,
+        };
+        return Some(value * METERS_IN_A_MILE);
+    }
+
+    // 4 digits, optionally followed by a direction or NDV
+    let digits = token.get(..4)?;
+    if token.len() <= 7 && digits.bytes().all(|b| b.is_ascii_digit()) {
+        let rest = &token[4..];
+        if rest.bytes().all(|b| b.is_ascii_alphabetic()) {
+            return digits.parse().ok();
+        }
+    }
+    None
+}
+
+/// Parses a cloud layer group (`SCT030`, `BKN012CB`) into its sky cover
+fn metar_cloud(token: &str) -> Option<f64> {
+    let cover = match token.get(..3)? {
+        "FEW" => 0.2,
+        "SCT" => 0.45,
+        "BKN" => 0.75,
+        "OVC" => 1.0,
+        _ => return None,
+    };
+
+    let height = token.get(3..6)?;
+    if height.bytes().all(|b| b.is_ascii_digit()) || height == "///" {
+        Some(cover)
+    } else {
+        None
+    }
+}
+
+/// Parses a temperature/dew point group (`18/09`, `M02/M05`)
+fn metar_temperature(token: &str) -> Option<f64> {
+    let (temperature, dew_point) = token.split_once('/')?;
+    let parse = |value: &str| -> Option<f64> {
+        let (sign, digits) = match value.strip_prefix('M') {
+            Some(digits) => (-1.0, digits),
+            None => (1.0, value),
+        };
+        if digits.len() != 2 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse::<f64>().ok().map(|v| sign * v)
+    };
+
+    if !dew_point.is_empty() {
+        parse(dew_point)?;
+    }
+    parse(temperature)
+}
+
+/// Parses a present weather group (`-SHRA`, `+SN`, `FG`, `RASN`) into what falls and the fog
+fn metar_weather(token: &str) -> Option<(Option<(Falling, f64)>, f64)> {
+    const DESCRIPTORS: [&str; 8] = ["MI", "PR", "BC", "DR", "BL", "SH", "TS", "FZ"];
+
+    let (intensity, rest) = match token.as_bytes().first()? {
+        b'-' => (0.3, &token[1..]),
+        b'+' => (1.0, &token[1..]),
+        _ => (0.6, token),
+    };
+    let rest = rest.strip_prefix("VC").unwrap_or(rest);
+    let rest = match rest.get(..2) {
+        Some(descriptor) if DESCRIPTORS.contains(&descriptor) => &rest[2..],
+        _ => rest,
+    };
+    if rest.is_empty() {
+        // a thunderstorm alone, without precipitation
+        return token.contains("TS").then_some((None, 0.0));
+    }
+    if rest.len() % 2 != 0 {
+        return None;
+    }
+
+    let (mut rain, mut snow, mut drizzle) = (false, false, false);
+    let mut obscuration: f64 = 0.0;
+    for i in (0..rest.len()).step_by(2) {
+        match rest.get(i..i + 2)? {
+            "RA" | "GR" | "GS" => rain = true,
+            "DZ" => drizzle = true,
+            "SN" | "SG" | "IC" | "PL" => snow = true,
+            "FG" => obscuration = obscuration.max(0.9),
+            "BR" => obscuration = obscuration.max(0.45),
+            "HZ" | "FU" | "DU" | "SA" | "VA" => obscuration = obscuration.max(0.25),
+            "UP" | "PO" | "SQ" | "FC" | "SS" | "DS" | "PY" => {}
+            _ => return None,
+        }
+    }
+
+    let falling = match (rain || drizzle, snow) {
+        (true, true) => Some(Falling::Mixed),
+        (false, true) => Some(Falling::Snow),
+        (true, false) if rain => Some(Falling::Rain),
+        (true, false) => Some(Falling::Drizzle),
+        (false, false) => None,
+    };
+    Some((falling.map(|f| (f, intensity)), obscuration))
+}
+
+/// Reads a weather report from a METAR observation
+///
+/// Only the cloud layers, the visibility, the present weather and the temperature are used,
+/// the remarks and trend forecasts are ignored.
+pub fn parse_metar(text: &str) -> Result<Conditions, ReportError> {
+    let mut observation = Observation::default();
+    let mut recognized = false;
+
+    // the report type, the station and the time of the observation come before the weather
+    let mut tokens = text.split_whitespace().peekable();
+    tokens.next_if(|token| matches!(*token, "METAR" | "SPECI"));
+    tokens.next_if(|token| matches!(*token, "COR" | "AMD"));
+    match tokens.next() {
+        Some(station)
+            if station.len() == 4 && station.bytes().all(|b| b.is_ascii_alphanumeric()) => {}
+        Some(token) => {
+            return Err(ReportError::Metar(format!(
+                "expected a station, found {}",
+                token
+            )))
+        }
+        None => return Err(ReportError::Metar("empty report".to_string())),
+    }
+    tokens.next_if(|token| {
+        token.len() == 7 && token.ends_with('Z') && token[..6].bytes().all(|b| b.is_ascii_digit())
+    });
+
+    for token in tokens {
+        if !token.is_ascii() {
+            return Err(ReportError::Metar(format!("unexpected group {}", token)));
+        }
+        match token {
+            "RMK" | "TEMPO" | "BECMG" | "NOSIG" => break,
+            "CAVOK" => {
+                observation.cloud_cover = Some(0.0);
+                observation.visibility = Some(10000.0);
+                recognized = true;
+                continue;
+            }
+            "SKC" | "CLR" | "NSC" | "NCD" => {
+                observation.cloud_cover.get_or_insert(0.0);
+                recognized = true;
+                continue;
+            }
+            _ => {}
+        }
+
+        if let Some(cover) = metar_cloud(token) {
+            let total = observation.cloud_cover.get_or_insert(0.0);
+            *total = total.max(cover);
+        } else if token.starts_with("VV") && token.len() == 5 {
+            // vertical visibility, the sky is hidden
+            observation.cloud_cover = Some(1.0);
+            observation.obscuration = observation.obscuration.max(0.9);
+        } else if let Some(visibility) = metar_visibility(token) {
+            observation.visibility.get_or_insert(visibility);
+        } else if let Some(temperature) = metar_temperature(token) {
+            observation.temperature = Some(temperature);
+        } else if let Some((falling, obscuration)) = metar_weather(token) {
+            if falling.is_some() {
+                observation.falling = falling;
+            }
+            observation.obscuration = observation.obscuration.max(obscuration);
+        } else {
+            continue;
+        }
+        recognized = true;
+    }
+
+    if !recognized {
+        return Err(ReportError::Metar("no weather group found".to_string()));
+    }
+    Ok(observation.conditions())
+}
+
+/// Reads a weather report file, either a JSON object or a METAR observation
+pub fn load(path: &Path) -> Result<Conditions, ReportError> {
+    let text = std::fs::read_to_string(path)?;
+
+    let conditions = if text.trim_start().starts_with('{') {
+        parse_json(&text)
+    } else {
+        parse_metar(&text)
+    }?;
+
+    log::debug!("Weather of {}: {:?}", path.display(), conditions);
+    Ok(conditions)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::weather::Precipitation;
+
+    #[test]
+    fn test_metar() {
+        let c = super::parse_metar("LFPG 121230Z 24008KT CAVOK 18/09 Q1015 NOSIG").unwrap();
+        assert_eq!(c.cloud_cover, 0.0);
+        assert_eq!(c.fog, 0.0);
+        assert_eq!(c.precipitation, None);
+
+        let c = super::parse_metar("EGLL 121250Z 20015KT 6000 -SHRA BKN012 OVC030 11/09 Q1002")
+            .unwrap();
+        assert_eq!(c.cloud_cover, 1.0);
+        assert_eq!(c.precipitation, Some(Precipitation::Rain));
+        assert!(c.intensity < 0.5, "{:?}", c);
+
+        let c = super::parse_metar("KJFK 121251Z 00000KT 1/4SM FG VV002 02/02 A3001").unwrap();
+        assert!(c.fog > 0.8, "{:?}", c);
+
+        // mixed precipitation is snow when it is cold enough
+        let c = super::parse_metar("ENGM 121250Z 36005KT 2000 RASN OVC008 M01/M02 Q0998").unwrap();
+        assert_eq!(c.precipitation, Some(Precipitation::Snow));
+        let c = super::parse_metar("ENGM 121250Z 36005KT 2000 RASN OVC008 04/02 Q0998").unwrap();
+        assert_eq!(c.precipitation, Some(Precipitation::Rain));
+
+        // the station is not a weather group
+        let c = super::parse_metar("METAR SNBR 121200Z 28/18").unwrap();
+        assert_eq!(c.precipitation, None);
+        assert_eq!(c.fog, 0.0);
+
+        assert!(super::parse_metar("hello world").is_err());
+        assert!(super::parse_metar("").is_err());
+        assert!(super::parse_metar("LFPG 121230Z xéx 24008KT CAVOK 18/09").is_err());
+    }
+
+    #[test]
+    fn test_json() {
+        let c = super::parse_json(
+            r#"{"cloud_cover": 0.6, "precipitation": "snow", "intensity": 0.8, "visibility": 3000}"#,
+        )
+        .unwrap();
+        assert_eq!(c.cloud_cover, 0.6);
+        assert_eq!(c.precipitation, Some(Precipitation::Snow));
+        assert_eq!(c.intensity, 0.8);
+        assert!(c.fog > 0.0 && c.desaturation > 0.0, "{:?}", c);
+
+        let c = super::parse_json("{}").unwrap();
+        assert_eq!(c.precipitation, None);
+
+        assert!(super::parse_json(r#"{"precipitation": "frogs"}"#).is_err());
+    }
+}
@@ -0,0 +1,287 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Utc};
+
+use crate::angle::Angle;
+use crate::camera::Camera;
+use crate::fit::{Anchor, Fit, Placement};
+use crate::render;
+use crate::sky::SkyState;
+use crate::theme::{Color, Theme};
+use crate::weather::Conditions;
+
+/// Where the scene is seen from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+    /// Expressed as degrees, positive to the north
+    pub latitude: f64,
+    /// Expressed as degrees, positive to the east
+    pub longitude: f64,
+    /// Expressed as meters above sea level
+    pub altitude: f64,
+}
+
+impl Location {
+    pub fn new(latitude: f64, longitude: f64, altitude: f64) -> Self {
+        Self {
+            latitude,
+            longitude,
+            altitude,
+        }
+    }
+}
+
+/// Largest width and height of the images drawn, in pixels
+pub const MAX_IMAGE_SIDE: u32 = 32768;
+
+/// Most pixels of the images drawn, a gigabyte of memory once rasterized
+pub const MAX_IMAGE_PIXELS: u64 = 1 << 28;
+
+/// The size of an image that cannot be drawn, either without pixels or too large
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeError {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl fmt::Display for SizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "cannot draw an image of {}x{} pixels, expected from 1 to {} pixels on each side and \
+             {} megapixels at most",
+            self.width,
+            self.height,
+            MAX_IMAGE_SIDE,
+            MAX_IMAGE_PIXELS / 1_000_000
+        )
+    }
+}
+
+impl std::error::Error for SizeError {}
+
+fn check_size(width: u32, height: u32) -> Result<(), SizeError> {
+    let sides = 1..=MAX_IMAGE_SIDE;
+    if !sides.contains(&width)
+        || !sides.contains(&height)
+        || width as u64 * height as u64 > MAX_IMAGE_PIXELS
+    {
+        return Err(SizeError { width, height });
+    }
+    Ok(())
+}
+
+/// A valley under a sky, along with how it is framed in the images drawn from it
+pub struct Scene {
+    sky: SkyState,
+    /// Parsed when first needed, the default theme unless another one is given
+    theme: OnceLock<Theme>,
+    fit: Fit,
+    anchor: Anchor,
+    camera: Camera,
+}
+
+impl Scene {
+    /// The scene seen from `location` at `datetime`, with the default theme and a clear sky
+    pub fn new(location: Location, datetime: DateTime<Utc>) -> Self {
+        Self::from_sky(SkyState::at(
+            datetime.naive_utc(),
+            location.latitude,
+            location.longitude,
+            location.altitude,
+        ))
+    }
+
+    /// The scene matching an angle of the palette, the sun going east to west
+    pub fn from_angle(angle: Angle) -> Self {
+        Self::from_sky(SkyState::from_angle(angle))
+    }
+
+    pub fn from_sky(sky: SkyState) -> Self {
+        Self {
+            sky,
+            theme: OnceLock::new(),
+            fit: Fit::default(),
+            anchor: Anchor::default(),
+            camera: Camera::default(),
+        }
+    }
+
+    pub fn with_theme(self, theme: Theme) -> Self {
+        Self {
+            theme: OnceLock::from(theme),
+            ..self
+        }
+    }
+
+    /// Draws the weather over the scene, the same `seed` always drawing the same clouds
+    pub fn with_weather(mut self, weather: Conditions, seed: u64) -> Self {
+        self.sky.weather = weather;
+        self.sky.seed = seed;
+        self
+    }
+
+    /// Sets how the scene fills images of another aspect ratio
+    pub fn with_fit(self, fit: Fit, anchor: Anchor) -> Self {
+        Self {
+            fit,
+            anchor,
+            ..self
+        }
+    }
+
+    pub fn with_camera(self, camera: Camera) -> Self {
+        Self { camera, ..self }
+    }
+
+    pub fn sky(&self) -> &SkyState {
+        &self.sky
+    }
+
+    pub fn theme(&self) -> &Theme {
+        self.theme.get_or_init(Theme::embedded)
+    }
+
+    /// Lays the scene out on an image of `width` by `height` pixels
+    pub fn frame(&self, width: u32, height: u32) -> Result<Frame, SizeError> {
+        check_size(width, height)?;
+
+        let theme = self.theme();
+        let placement = Placement::new(self.fit, self.anchor, &self.camera, width, height);
+        Ok(Frame {
+            tree: render::render_extended(&self.sky, theme, placement.margins, self.camera.mirror),
+            background: theme.palette.background,
+            placement,
+            width,
+            height,
+        })
+    }
+
+    /// Draws the scene on an image of `width` by `height` pixels
+    pub fn render(&self, width: u32, height: u32) -> Result<tiny_skia::Pixmap, SizeError> {
+        self.frame(width, height)?.render()
+    }
+
+    /// The scene as a vector image of `width` by `height` pixels
+    pub fn tree(&self, width: u32, height: u32) -> Result<usvg::Tree, SizeError> {
+        self.frame(width, height)?.tree()
+    }
+}
+
+/// A scene laid out on an image, which can be drawn whole or in parts, such as the monitors of
+/// a layout
+pub struct Frame {
+    tree: usvg::Tree,
+    background: Color,
+    placement: Placement,
+    width: u32,
+    height: u32,
+}
+
+impl Frame {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Color of the theme behind the parts of the image the scene leaves uncovered, for the
+    /// images without transparency
+    pub fn background(&self) -> Color {
+        self.background
+    }
+
+    pub fn render(&self) -> Result<tiny_skia::Pixmap, SizeError> {
+        self.render_part(0, 0, self.width, self.height)
+    }
+
+    pub fn tree(&self) -> Result<usvg::Tree, SizeError> {
+        self.tree_part(0, 0, self.width, self.height)
+    }
+
+    /// Draws the part of the image of `width` by `height` pixels starting at `x`, `y`
+    pub fn render_part(
+        &self,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    ) -> Result<tiny_skia::Pixmap, SizeError> {
+        check_size(width, height)?;
+
+        let usvg::Transform { a, b, c, d, e, f } = self.part_transform(x, y);
+        let transform = tiny_skia::Transform::from_row(
+            a as f32, b as f32, c as f32, d as f32, e as f32, f as f32,
+        );
+
+        let error = SizeError { width, height };
+        let mut pixmap = tiny_skia::Pixmap::new(width, height).ok_or(error)?;
+        resvg::render(
+            &self.tree,
+            usvg::FitTo::Original,
+            transform,
+            pixmap.as_mut(),
+        )
+        .ok_or(error)?;
+        Ok(pixmap)
+    }
+
+    /// The part of the image of `width` by `height` pixels starting at `x`, `y`, as a vector
+    /// image
+    pub fn tree_part(
+        &self,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    ) -> Result<usvg::Tree, SizeError> {
+        check_size(width, height)?;
+        Ok(render::fit_tree(
+            &self.tree,
+            width,
+            height,
+            self.part_transform(x, y),
+        ))
+    }
+
+    fn part_transform(&self, x: i32, y: i32) -> usvg::Transform {
+        let mut transform = self.placement.transform;
+        transform.e -= x as f64;
+        transform.f -= y as f64;
+        transform
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scene;
+
+    #[test]
+    fn test_frame() {
+        let scene = Scene::from_angle(95.0);
+        let frame = scene.frame(64, 36).unwrap();
+
+        // a part of the frame is drawn the same as the whole frame
+        let whole = frame.render().unwrap();
+        let part = frame.render_part(32, 0, 32, 36).unwrap();
+        for y in 0..36 {
+            assert_eq!(whole.pixel(32 + y % 32, y), part.pixel(y % 32, y));
+        }
+
+        let tree = frame.tree_part(32, 0, 32, 36).unwrap();
+        assert_eq!(tree.svg_node().size.width(), 32.0);
+
+        // images without pixels or too large are refused rather than drawn
+        assert!(frame.render_part(0, 0, 0, 36).is_err());
+        assert!(frame.tree_part(0, 0, 32, 0).is_err());
+        assert!(frame.render_part(0, 0, u32::MAX / 2, 36).is_err());
+        assert!(frame.render_part(0, 0, 30000, 30000).is_err());
+        assert!(scene.frame(0, 0).is_err());
+        assert!(scene.frame(100_000, 100_000).is_err());
+        let scene = scene.with_fit(super::Fit::Smart, super::Anchor::Center);
+        assert!(scene.render(0, 1080).is_err());
+    }
+}
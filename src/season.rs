@@ -0,0 +1,101 @@
+use chrono::{Datelike, NaiveDate};
+
+const DAYS_IN_A_YEAR: f64 = 365.25;
+
+/// Day of the year (from 0) at the heart of each northern season, from winter to autumn
+const SEASON_CENTERS: [f64; 4] = [14.0, 104.0, 195.0, 287.0];
+
+/// Latitudes closer to the equator than this have no seasons, only summer
+const TROPICS: f64 = 10.0;
+
+/// Latitudes further from the equator than this get the full seasons
+const TEMPERATE: f64 = 30.0;
+
+/// How much each season shows in the landscape, the weights sum to 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Season {
+    pub winter: f64,
+    pub spring: f64,
+    pub summer: f64,
+    pub autumn: f64,
+}
+
+impl Default for Season {
+    /// The landscape palettes as they are
+    fn default() -> Self {
+        Self {
+            winter: 0.0,
+            spring: 0.0,
+            summer: 1.0,
+            autumn: 0.0,
+        }
+    }
+}
+
+impl Season {
+    /// Calculates the season on a given date, blending the two closest seasons
+    ///
+    /// # Arguments
+    ///
+    /// * `date` - The date of the landscape
+    /// * `latitude` - The latitude of the observer, its sign gives the hemisphere. Expressed as degrees
+    pub fn at(date: NaiveDate, latitude: f64) -> Self {
+        let mut day = date.ordinal0() as f64;
+        if latitude < 0.0 {
+            day += DAYS_IN_A_YEAR / 2.0;
+        }
+        let day = day.rem_euclid(DAYS_IN_A_YEAR);
+
+        let mut weights = [0.0; 4];
+        for i in 0..4 {
+            let start = SEASON_CENTERS[i];
+            let end = SEASON_CENTERS[(i + 1) % 4];
+            let length = (end - start).rem_euclid(DAYS_IN_A_YEAR);
+            let elapsed = (day - start).rem_euclid(DAYS_IN_A_YEAR);
+
+            if elapsed < length {
+                weights[i] = 1.0 - elapsed / length;
+                weights[(i + 1) % 4] = elapsed / length;
+            }
+        }
+
+        // seasons fade out toward the equator
+        let strength = ((latitude.abs() - TROPICS) / (TEMPERATE - TROPICS)).clamp(0.0, 1.0);
+
+        Self {
+            winter: weights[0] * strength,
+            spring: weights[1] * strength,
+            summer: weights[2] * strength + (1.0 - strength),
+            autumn: weights[3] * strength,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::Season;
+
+    #[test]
+    fn test_season() {
+        let january = NaiveDate::from_ymd_opt(2023, 1, 15).unwrap();
+        let season = Season::at(january, 48.86);
+        assert!(season.winter > 0.99, "{:?}", season);
+
+        // the seasons are reversed in the southern hemisphere
+        let season = Season::at(january, -33.87);
+        assert!(season.summer > 0.95, "{:?}", season);
+
+        // halfway between autumn and winter
+        let december = NaiveDate::from_ymd_opt(2023, 12, 1).unwrap();
+        let season = Season::at(december, 48.86);
+        assert!(season.autumn > 0.4 && season.winter > 0.4, "{:?}", season);
+        let sum = season.winter + season.spring + season.summer + season.autumn;
+        assert!((sum - 1.0).abs() < 1e-9, "{:?}", season);
+
+        // no winter on the equator
+        let season = Season::at(january, 1.35);
+        assert_eq!(season, Season::default());
+    }
+}
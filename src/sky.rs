@@ -0,0 +1,233 @@
+use chrono::NaiveDateTime;
+
+use crate::angle::{self, Angle, PaletteAngle};
+use crate::moon::{self, MoonPosition};
+use crate::season::Season;
+use crate::solar;
+use crate::sunrise;
+use crate::weather::Conditions;
+
+/// Latitude of the sky drawn when no place is given
+const DEFAULT_LATITUDE: f64 = 45.0;
+
+/// Sidereal time of the sky drawn when no place is given, Orion on the meridian
+const DEFAULT_SIDEREAL_TIME: f64 = 83.0;
+
+/// Azimuths on each side of a culmination over which the palettes go from the morning half to
+/// the evening half
+const CULMINATION: f64 = 15.0;
+
+/// Apparent magnitude of the sun
+const SUN_MAGNITUDE: f64 = -26.74;
+
+/// Faintest magnitude seen with the naked eye for a given sun elevation, the sky is darker
+/// below the first elevation and no brighter above the last one
+const LIMITING_MAGNITUDE: [(f64, f64); 4] = [
+    (sunrise::ASTRONOMICAL_TWILIGHT, 6.0),
+    (sunrise::NAUTICAL_TWILIGHT, 3.5),
+    (sunrise::CIVIL_TWILIGHT, 1.0),
+    (sunrise::SUNRISE, -4.0),
+];
+
+/// Magnitudes over which an object fades in above the limiting magnitude
+const FADE_MAGNITUDES: f64 = 1.0;
+
+/// Brightness of the sky, from the sun elevation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Night,
+    AstronomicalTwilight,
+    NauticalTwilight,
+    CivilTwilight,
+    Day,
+}
+
+impl Phase {
+    pub fn from_elevation(elevation_deg: f64) -> Self {
+        if elevation_deg >= sunrise::SUNRISE {
+            Phase::Day
+        } else if elevation_deg >= sunrise::CIVIL_TWILIGHT {
+            Phase::CivilTwilight
+        } else if elevation_deg >= sunrise::NAUTICAL_TWILIGHT {
+            Phase::NauticalTwilight
+        } else if elevation_deg >= sunrise::ASTRONOMICAL_TWILIGHT {
+            Phase::AstronomicalTwilight
+        } else {
+            Phase::Night
+        }
+    }
+}
+
+/// Where the sun is in the sky, everything the renderer needs to draw a scene
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SkyState {
+    /// Apparent elevation of the sun above the horizon. Expressed as degrees
+    pub elevation_deg: f64,
+    /// Azimuth of the sun, clockwise from the north. Expressed as degrees
+    pub azimuth_deg: f64,
+    pub phase: Phase,
+    /// Where the moon is, `None` to leave it out of the scene
+    pub moon: Option<MoonPosition>,
+    /// Latitude of the observer, which sets the stars in view. Expressed as degrees
+    pub latitude: f64,
+    /// Local sidereal time, which turns the stars around the pole. Expressed as degrees
+    pub sidereal_time: f64,
+    /// Season of the landscape
+    pub season: Season,
+    /// Conditions drawn over the scene
+    pub weather: Conditions,
+    /// Seed of the weather shapes, the same seed always draws the same clouds
+    pub seed: u64,
+}
+
+impl SkyState {
+    pub fn new(elevation_deg: f64, azimuth_deg: f64) -> Self {
+        Self {
+            elevation_deg,
+            azimuth_deg,
+            phase: Phase::from_elevation(elevation_deg),
+            moon: None,
+            latitude: DEFAULT_LATITUDE,
+            sidereal_time: DEFAULT_SIDEREAL_TIME,
+            season: Season::default(),
+            weather: Conditions::default(),
+            seed: 0,
+        }
+    }
+
+    /// Calculates the sky at a given instant and place
+    ///
+    /// # Arguments
+    ///
+    /// * `datetime` - The UTC instant to render
+    /// * `latitude` - The latitude of the observer. Expressed as degrees
+    /// * `longitude` - The longitude of the observer. Expressed as degrees
+    /// * `elevation` - The elevation of the observer. Expressed as meters above sea level, the
+    ///   sun is measured from the lowered horizon seen from there
+    pub fn at(datetime: NaiveDateTime, latitude: f64, longitude: f64, elevation: f64) -> Self {
+        let position = solar::position(datetime, latitude, longitude);
+        let dip = 2.076 * elevation.max(0.0).sqrt() / 60.0;
+
+        Self {
+            moon: Some(moon::position(datetime, latitude, longitude)),
+            latitude,
+            sidereal_time: solar::local_sidereal_time(datetime, longitude),
+            season: Season::at(datetime.date(), latitude),
+            ..Self::new(position.elevation + dip, position.azimuth)
+        }
+    }
+
+    /// Builds the sky matching an angle of the palette, the sun going east to west
+    pub fn from_angle(angle: Angle) -> Self {
+        let (elevation_deg, rising) = angle::angle_elevation(angle);
+        let azimuth_deg = if rising { 90.0 } else { 270.0 };
+
+        Self::new(elevation_deg, azimuth_deg)
+    }
+
+    /// The sun is rising while it is in the eastern half of the sky, in both hemispheres
+    pub fn is_rising(&self) -> bool {
+        self.azimuth_deg < 180.0
+    }
+
+    /// Faintest magnitude visible in this sky
+    pub fn limiting_magnitude(&self) -> f64 {
+        let elevation = self.elevation_deg;
+        let (first, last) = (LIMITING_MAGNITUDE[0], LIMITING_MAGNITUDE[3]);
+        if elevation <= first.0 {
+            return first.1;
+        }
+        if elevation >= last.0 {
+            return last.1;
+        }
+
+        let i = LIMITING_MAGNITUDE
+            .iter()
+            .rposition(|&(e, _)| e <= elevation)
+            .unwrap();
+        let (e1, m1) = LIMITING_MAGNITUDE[i];
+        let (e2, m2) = LIMITING_MAGNITUDE[i + 1];
+        m1 + (m2 - m1) * (elevation - e1) / (e2 - e1)
+    }
+
+    /// How visible an object is in this sky, from 0 (hidden) to 1 (fully visible)
+    ///
+    /// Objects fade in as the sky darkens below the sun, and are dimmed near the horizon by the
+    /// thicker atmosphere they are seen through.
+    ///
+    /// # Arguments
+    ///
+    /// * `magnitude` - The apparent magnitude of the object
+    /// * `altitude` - The apparent altitude of the object. Expressed as degrees
+    pub fn visibility(&self, magnitude: f64, altitude: f64) -> f64 {
+        if altitude < 0.0 {
+            return 0.0;
+        }
+
+        // about 0.2 magnitude per airmass, capped a degree above the horizon
+        let airmass = 1.0 / altitude.max(1.0).to_radians().sin();
+        let extinction = 0.2 * (airmass - 1.0);
+
+        ((self.limiting_magnitude() - magnitude - extinction) / FADE_MAGNITUDES).clamp(0.0, 1.0)
+    }
+
+    /// Visibility of the sun, which stays drawn as long as its upper limb is above the horizon
+    pub fn sun_visibility(&self) -> f64 {
+        self.visibility(SUN_MAGNITUDE, self.elevation_deg - sunrise::SUNRISE)
+    }
+
+    /// Nearest position in the palettes for this sky, on the half of the palettes the sun is in
+    pub fn angle(&self) -> Angle {
+        angle::elevation_angle(self.elevation_deg, self.is_rising())
+    }
+
+    /// Position in the palettes for this sky, going smoothly from the morning half to the evening
+    /// half through the culminations
+    pub fn palette_angle(&self) -> PaletteAngle {
+        // the sun culminates when it crosses the meridian, at an azimuth of 180 or 0
+        let azimuth = self.azimuth_deg.rem_euclid(360.0);
+        let step = |x: f64| {
+            let x = x.clamp(0.0, 1.0);
+            x * x * (3.0 - 2.0 * x)
+        };
+        let evening = if azimuth < 90.0 {
+            step((CULMINATION - azimuth) / (2.0 * CULMINATION))
+        } else if azimuth < 270.0 {
+            step((azimuth - 180.0 + CULMINATION) / (2.0 * CULMINATION))
+        } else {
+            step((360.0 + CULMINATION - azimuth) / (2.0 * CULMINATION))
+        };
+
+        PaletteAngle::new(self.elevation_deg, evening)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SkyState;
+
+    #[test]
+    fn test_visibility() {
+        // Sirius shows up during the nautical twilight, a 4th magnitude star does not
+        let sky = SkyState::new(-9.0, 270.0);
+        assert!(sky.visibility(-1.46, 30.0) > 0.9);
+        assert_eq!(sky.visibility(4.0, 30.0), 0.0);
+
+        // and every catalog star is out at night, except right at the horizon
+        let sky = SkyState::new(-30.0, 0.0);
+        assert!(sky.visibility(5.0, 45.0) > 0.5);
+        assert_eq!(sky.visibility(2.0, 0.5), 0.0);
+
+        // the order is the same at dawn
+        let dusk = SkyState::new(-10.0, 270.0);
+        let dawn = SkyState::new(-10.0, 90.0);
+        assert_eq!(dusk.visibility(2.0, 30.0), dawn.visibility(2.0, 30.0));
+    }
+
+    #[test]
+    fn test_sun_visibility() {
+        assert_eq!(SkyState::new(20.0, 180.0).sun_visibility(), 1.0);
+        assert_eq!(SkyState::new(-0.5, 270.0).sun_visibility(), 1.0);
+        assert_eq!(SkyState::new(-2.0, 270.0).sun_visibility(), 0.0);
+    }
+}
@@ -0,0 +1,269 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime, Timelike};
+
+const JULIAN_UNIX_EPOCH: f64 = 2440587.5;
+const JULIAN_J2000: f64 = 2451545.0;
+const SECONDS_IN_A_DAY: f64 = 86400.0;
+const MINUTES_IN_A_DAY: f64 = 1440.0;
+
+/// Position of the sun in the local sky
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SolarPosition {
+    /// Apparent elevation above the horizon, corrected for atmospheric refraction. Expressed as degrees
+    pub elevation: f64,
+    /// Azimuth measured clockwise from the north. Expressed as degrees in `[0, 360)`
+    pub azimuth: f64,
+    /// Local hour angle, negative in the morning and positive in the afternoon. Expressed as degrees
+    pub hour_angle: f64,
+}
+
+/// Intermediate values of the NOAA solar calculator for a given instant
+struct SolarCoordinates {
+    /// Declination of the sun, in degrees
+    declination: f64,
+    /// Equation of time, in minutes
+    equation_of_time: f64,
+}
+
+/// Converts a UTC instant to a julian day
+pub fn julian_day(datetime: NaiveDateTime) -> f64 {
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    let elapsed = datetime.signed_duration_since(epoch);
+    let timestamp = elapsed.num_seconds() as f64
+        + (elapsed - Duration::seconds(elapsed.num_seconds()))
+            .num_nanoseconds()
+            .unwrap() as f64
+            / 1_000_000_000.0;
+    timestamp / SECONDS_IN_A_DAY + JULIAN_UNIX_EPOCH
+}
+
+fn minutes_of_day(datetime: NaiveDateTime) -> f64 {
+    datetime.num_seconds_from_midnight() as f64 / 60.0
+}
+
+fn solar_coordinates(datetime: NaiveDateTime) -> SolarCoordinates {
+    //see https://gml.noaa.gov/grad/solcalc/calcdetails.html
+
+    let t = (julian_day(datetime) - JULIAN_J2000) / 36525.0;
+
+    let mean_longitude = (280.46646 + t * (36000.76983 + t * 0.0003032)).rem_euclid(360.0);
+    let mean_anomaly = 357.52911 + t * (35999.05029 - 0.0001537 * t);
+    let eccentricity = 0.016708634 - t * (0.000042037 + 0.0000001267 * t);
+
+    let m = mean_anomaly.to_radians();
+    let center = m.sin() * (1.914602 - t * (0.004817 + 0.000014 * t))
+        + (2.0 * m).sin() * (0.019993 - 0.000101 * t)
+        + (3.0 * m).sin() * 0.000289;
+
+    let true_longitude = mean_longitude + center;
+    let omega = (125.04 - 1934.136 * t).to_radians();
+    let apparent_longitude = true_longitude - 0.00569 - 0.00478 * omega.sin();
+
+    let mean_obliquity =
+        23.0 + (26.0 + (21.448 - t * (46.815 + t * (0.00059 - t * 0.001813))) / 60.0) / 60.0;
+    let obliquity = (mean_obliquity + 0.00256 * omega.cos()).to_radians();
+
+    let declination = (obliquity.sin() * apparent_longitude.to_radians().sin())
+        .asin()
+        .to_degrees();
+
+    let y = (obliquity / 2.0).tan().powi(2);
+    let l0 = mean_longitude.to_radians();
+    let equation_of_time = 4.0
+        * (y * (2.0 * l0).sin() - 2.0 * eccentricity * m.sin()
+            + 4.0 * eccentricity * y * m.sin() * (2.0 * l0).cos()
+            - 0.5 * y * y * (4.0 * l0).sin()
+            - 1.25 * eccentricity * eccentricity * (2.0 * m).sin())
+        .to_degrees();
+
+    SolarCoordinates {
+        declination,
+        equation_of_time,
+    }
+}
+
+/// Approximate correction of the apparent elevation for atmospheric refraction, in degrees
+pub fn refraction(elevation: f64) -> f64 {
+    if elevation > 85.0 {
+        return 0.0;
+    }
+
+    let te = elevation.to_radians().tan();
+    let arc_seconds = if elevation > 5.0 {
+        58.1 / te - 0.07 / te.powi(3) + 0.000086 / te.powi(5)
+    } else if elevation > -0.575 {
+        1735.0
+            + elevation * (-518.2 + elevation * (103.4 + elevation * (-12.79 + elevation * 0.711)))
+    } else {
+        -20.772 / te
+    };
+
+    arc_seconds / 3600.0
+}
+
+/// Calculates the local mean sidereal time. Expressed as degrees in `[0, 360)`
+pub fn local_sidereal_time(datetime: NaiveDateTime, longitude: f64) -> f64 {
+    let d = julian_day(datetime) - JULIAN_J2000;
+    let t = d / 36525.0;
+
+    let greenwich =
+        280.46061837 + 360.98564736629 * d + 0.000387933 * t * t - t * t * t / 38710000.0;
+    (greenwich + longitude).rem_euclid(360.0)
+}
+
+/// Converts equatorial coordinates to the local `(altitude, azimuth)`, without refraction
+///
+/// # Arguments
+///
+/// * `right_ascension` - Expressed as degrees
+/// * `declination` - Expressed as degrees
+/// * `sidereal_time` - The local sidereal time, see `local_sidereal_time`. Expressed as degrees
+/// * `latitude` - The latitude of the observer. Expressed as degrees
+pub fn horizontal(
+    right_ascension: f64,
+    declination: f64,
+    sidereal_time: f64,
+    latitude: f64,
+) -> (f64, f64) {
+    let ha = (sidereal_time - right_ascension).to_radians();
+    let dec = declination.to_radians();
+    let lat = latitude.to_radians();
+
+    let altitude = (lat.sin() * dec.sin() + lat.cos() * dec.cos() * ha.cos())
+        .clamp(-1.0, 1.0)
+        .asin();
+    let azimuth = ha
+        .sin()
+        .atan2(ha.cos() * lat.sin() - dec.tan() * lat.cos())
+        .to_degrees()
+        + 180.0;
+
+    (altitude.to_degrees(), azimuth.rem_euclid(360.0))
+}
+
+/// Calculates the true position of the sun at a given instant and place
+///
+/// # Arguments
+///
+/// * `datetime` - The UTC instant at which to calculate the position
+/// * `latitude` - The latitude of the observer. Expressed as degrees
+/// * `longitude` - The longitude of the observer. Expressed as degrees, positive to the east
+pub fn position(datetime: NaiveDateTime, latitude: f64, longitude: f64) -> SolarPosition {
+    let coords = solar_coordinates(datetime);
+
+    let true_solar_time = (minutes_of_day(datetime) + coords.equation_of_time + 4.0 * longitude)
+        .rem_euclid(MINUTES_IN_A_DAY);
+    let hour_angle = true_solar_time / 4.0 - 180.0;
+
+    let lat = latitude.to_radians();
+    let dec = coords.declination.to_radians();
+    let ha = hour_angle.to_radians();
+
+    let cos_zenith = (lat.sin() * dec.sin() + lat.cos() * dec.cos() * ha.cos()).clamp(-1.0, 1.0);
+    let zenith = cos_zenith.acos();
+    let elevation = 90.0 - zenith.to_degrees();
+
+    let denominator = lat.cos() * zenith.sin();
+    let azimuth = if denominator.abs() < 1e-9 {
+        // sun at the zenith, or observer on a pole
+        if latitude > 0.0 {
+            180.0
+        } else {
+            0.0
+        }
+    } else {
+        let a = ((lat.sin() * cos_zenith - dec.sin()) / denominator)
+            .clamp(-1.0, 1.0)
+            .acos()
+            .to_degrees();
+        if hour_angle > 0.0 {
+            (a + 180.0).rem_euclid(360.0)
+        } else {
+            (540.0 - a).rem_euclid(360.0)
+        }
+    };
+
+    SolarPosition {
+        elevation: elevation + refraction(elevation),
+        azimuth,
+        hour_angle,
+    }
+}
+
+/// Calculates the instant the sun crosses the local meridian on a given date
+pub fn solar_noon(date: NaiveDate, longitude: f64) -> NaiveDateTime {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+
+    // first guess at mean noon, then refine with the equation of time at that instant
+    let mut noon = midnight + Duration::minutes((720.0 - 4.0 * longitude).round() as i64);
+    for _ in 0..2 {
+        let coords = solar_coordinates(noon);
+        let minutes = 720.0 - 4.0 * longitude - coords.equation_of_time;
+        noon = midnight + Duration::seconds((minutes * 60.0).round() as i64);
+    }
+    noon
+}
+
+/// Calculates the hour angle at which the sun center reaches a given elevation
+///
+/// The elevation is geometric, so callers are expected to include the refraction and the
+/// sun radius themselves (e.g. `-0.833` for the usual sunrise definition).
+/// Returns `None` when the sun never crosses this elevation on that day.
+pub fn hour_angle(datetime: NaiveDateTime, latitude: f64, elevation: f64) -> Option<f64> {
+    let coords = solar_coordinates(datetime);
+
+    let lat = latitude.to_radians();
+    let dec = coords.declination.to_radians();
+
+    let cos_hour_angle =
+        (elevation.to_radians().sin() - lat.sin() * dec.sin()) / (lat.cos() * dec.cos());
+
+    if (-1.0..=1.0).contains(&cos_hour_angle) {
+        Some(cos_hour_angle.acos().to_degrees())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    const PARIS_LAT: f64 = 48.864716;
+    const PARIS_LON: f64 = 2.349014;
+
+    #[test]
+    fn test_julian_day() {
+        let j2000 = Utc.with_ymd_and_hms(2000, 1, 1, 12, 0, 0).unwrap();
+        assert_eq!(super::julian_day(j2000.naive_utc()), 2451545.0);
+
+        let d = NaiveDate::from_ymd_opt(1969, 12, 31)
+            .unwrap()
+            .and_hms_milli_opt(18, 0, 0, 500)
+            .unwrap();
+        let expected = 2440587.25 + 0.5 / 86400.0;
+        assert!((super::julian_day(d) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_position() {
+        let d = Utc.with_ymd_and_hms(2022, 6, 21, 12, 0, 0).unwrap();
+        let p = super::position(d.naive_utc(), PARIS_LAT, PARIS_LON);
+        assert!((p.elevation - 64.5).abs() < 0.2, "{:?}", p);
+        assert!((p.azimuth - 184.0).abs() < 0.5, "{:?}", p);
+
+        let d = Utc.with_ymd_and_hms(2022, 12, 21, 23, 0, 0).unwrap();
+        let p = super::position(d.naive_utc(), PARIS_LAT, PARIS_LON);
+        assert!(p.elevation < -60.0, "{:?}", p);
+    }
+
+    #[test]
+    fn test_solar_noon() {
+        let date = NaiveDate::from_ymd_opt(2022, 11, 3).unwrap();
+        let noon = super::solar_noon(date, 0.0);
+        let expected = date.and_hms_opt(11, 43, 35).unwrap();
+        assert!((noon - expected).num_seconds().abs() < 30, "{}", noon);
+    }
+}
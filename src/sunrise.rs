@@ -1,8 +1,74 @@
 use chrono::{Duration, NaiveDate, NaiveDateTime};
 
-const HOUR_ANGLE_TO_MINUTES_FACTOR: f64 = 4.0;
+use crate::solar;
 
-/// Calculates the approximate sunset and sunrise times at a given latitude, longitude, and altitude
+const HOUR_ANGLE_TO_SECONDS_FACTOR: f64 = 240.0;
+
+/// Apparent elevation of the sun center at sunrise and sunset, refraction and sun radius included
+pub const SUNRISE: f64 = -0.833;
+
+/// Sun elevation at the boundary between civil twilight and nautical twilight
+pub const CIVIL_TWILIGHT: f64 = -6.0;
+
+/// Sun elevation at the boundary between nautical twilight and astronomical twilight
+pub const NAUTICAL_TWILIGHT: f64 = -12.0;
+
+/// Sun elevation at the boundary between astronomical twilight and the night
+pub const ASTRONOMICAL_TWILIGHT: f64 = -18.0;
+
+/// Crossings of a sun elevation during a single day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SunEvents {
+    /// The sun goes through the elevation once rising and once setting
+    Normal {
+        rise: NaiveDateTime,
+        set: NaiveDateTime,
+    },
+    /// The sun stays above the elevation the whole day (e.g. midnight sun)
+    AlwaysUp,
+    /// The sun stays below the elevation the whole day (e.g. polar night)
+    AlwaysDown,
+}
+
+/// Calculates when the sun center crosses a given elevation around the solar noon of `date`
+///
+/// # Arguments
+///
+/// * `date` - The date on which to calculate the events
+/// * `latitude` - The latitude at which to calculate the times. Expressed as degrees
+/// * `longitude` - The longitude at which to calculate the times. Expressed as degrees
+/// * `sun_elevation` - The apparent elevation of the sun center. Expressed as degrees
+pub fn sun_events(date: NaiveDate, latitude: f64, longitude: f64, sun_elevation: f64) -> SunEvents {
+    let solar_transit = solar::solar_noon(date, longitude);
+
+    let hour_angle = match solar::hour_angle(solar_transit, latitude, sun_elevation) {
+        Some(hour_angle) => hour_angle,
+        None => {
+            let noon = solar::position(solar_transit, latitude, longitude);
+            return if noon.elevation > sun_elevation {
+                SunEvents::AlwaysUp
+            } else {
+                SunEvents::AlwaysDown
+            };
+        }
+    };
+    let offset = Duration::seconds((hour_angle * HOUR_ANGLE_TO_SECONDS_FACTOR).round() as i64);
+
+    // the declination moves during the day, so evaluate it again at each event
+    let rise_angle = solar::hour_angle(solar_transit - offset, latitude, sun_elevation);
+    let rise_angle = rise_angle.unwrap_or(hour_angle);
+    let rise = solar_transit
+        - Duration::seconds((rise_angle * HOUR_ANGLE_TO_SECONDS_FACTOR).round() as i64);
+
+    let set_angle = solar::hour_angle(solar_transit + offset, latitude, sun_elevation);
+    let set_angle = set_angle.unwrap_or(hour_angle);
+    let set = solar_transit
+        + Duration::seconds((set_angle * HOUR_ANGLE_TO_SECONDS_FACTOR).round() as i64);
+
+    SunEvents::Normal { rise, set }
+}
+
+/// Calculates the sunset and sunrise times at a given latitude, longitude, and altitude
 ///
 /// # Arguments
 ///
@@ -13,61 +79,145 @@ const HOUR_ANGLE_TO_MINUTES_FACTOR: f64 = 4.0;
 ///
 /// # Return value
 ///
-/// Returns a tuple of `(sunrise,sunset)`
+/// Returns `SunEvents::Normal` with the sunrise and sunset in UTC around the solar noon of
+/// `date`, or `AlwaysUp`/`AlwaysDown` when the sun does not cross the horizon that day
 ///
 /// # Examples
 ///
 /// ```
+/// use chrono::Utc;
+/// use goldvalley::sunrise::{sun_times, SunEvents};
+///
 /// //Calculate the sunset and sunrise times today at Sheffield university's new computer science building
-/// let times = sun_times(Utc::today(),53.38,-1.48,100.0);
-/// println!("Sunrise: {}, Sunset: {}",times.0,times.1);
+/// if let SunEvents::Normal { rise, set } = sun_times(Utc::now().date_naive(),53.38,-1.48,100.0) {
+///     println!("Sunrise: {}, Sunset: {}",rise,set);
+/// }
 /// ```
-pub fn sun_times(
-    date: NaiveDate,
+pub fn sun_times(date: NaiveDate, latitude: f64, longitude: f64, elevation: f64) -> SunEvents {
+    let elevation_correction = -2.076 * (elevation.sqrt()) / 60.0;
+    sun_events(date, latitude, longitude, SUNRISE + elevation_correction)
+}
+
+/// Calculates the sunrise and sunset of the solar day containing `datetime`, within half a day
+/// of its noon
+pub fn sun_times_at(
+    datetime: NaiveDateTime,
     latitude: f64,
     longitude: f64,
     elevation: f64,
-) -> (NaiveDateTime, NaiveDateTime) {
-    //see https://en.wikipedia.org/wiki/Sunrise_equation
+) -> SunEvents {
+    // events are computed around the solar noon, so pick the day by local mean time
+    let local_date = (datetime + Duration::seconds((longitude * 240.0).round() as i64)).date();
+    let noon = solar::solar_noon(local_date, longitude);
 
-    const ARGUMENT_OF_PERIHELION: f64 = 102.9372;
+    // and make sure we are within half a day of this noon
+    let date = if datetime - noon > Duration::hours(12) {
+        local_date.succ_opt().unwrap()
+    } else if noon - datetime > Duration::hours(12) {
+        local_date.pred_opt().unwrap()
+    } else {
+        local_date
+    };
+    sun_times(date, latitude, longitude, elevation)
+}
 
-    let elevation_correction = -2.076 * (elevation.sqrt()) / 60.0;
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    use super::SunEvents;
+
+    const TOKYO_LAT: f64 = 35.6762;
+    const TOKYO_LON: f64 = 139.6503;
+
+    const LA_LAT: f64 = 34.0522;
+    const LA_LON: f64 = -118.2437;
+
+    const TROMSO_LAT: f64 = 69.6492;
+    const TROMSO_LON: f64 = 18.9553;
+
+    fn assert_close(actual: NaiveDateTime, expected: NaiveDateTime) {
+        let delta = (actual - expected).num_seconds().abs();
+        assert!(delta < 120, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn test_sun_times() {
+        // Tokyo, 2022-01-01: sunrise 06:51 JST, sunset 16:38 JST
+        let date = NaiveDate::from_ymd_opt(2022, 1, 1).unwrap();
+        let SunEvents::Normal { rise, set } = super::sun_times(date, TOKYO_LAT, TOKYO_LON, 0.0)
+        else {
+            panic!("the sun rises in Tokyo");
+        };
+        assert_close(
+            rise,
+            NaiveDate::from_ymd_opt(2021, 12, 31)
+                .unwrap()
+                .and_hms_opt(21, 51, 0)
+                .unwrap(),
+        );
+        assert_close(set, date.and_hms_opt(7, 38, 0).unwrap());
+
+        // Los Angeles, 2022-01-01: sunrise 06:59 PST, sunset 16:55 PST
+        let SunEvents::Normal { rise, set } = super::sun_times(date, LA_LAT, LA_LON, 0.0) else {
+            panic!("the sun rises in Los Angeles");
+        };
+        assert_close(rise, date.and_hms_opt(14, 59, 0).unwrap());
+        assert_close(
+            set,
+            NaiveDate::from_ymd_opt(2022, 1, 2)
+                .unwrap()
+                .and_hms_opt(0, 55, 0)
+                .unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_polar_sun_times() {
+        let date = NaiveDate::from_ymd_opt(2022, 6, 21).unwrap();
+        let events = super::sun_times(date, TROMSO_LAT, TROMSO_LON, 0.0);
+        assert_eq!(events, SunEvents::AlwaysUp);
+
+        let date = NaiveDate::from_ymd_opt(2022, 12, 21).unwrap();
+        let events = super::sun_times(date, TROMSO_LAT, TROMSO_LON, 0.0);
+        assert_eq!(events, SunEvents::AlwaysDown);
+
+        // the last sunset of the autumn is still a normal day
+        let date = NaiveDate::from_ymd_opt(2022, 11, 20).unwrap();
+        let events = super::sun_times(date, TROMSO_LAT, TROMSO_LON, 0.0);
+        assert!(matches!(events, SunEvents::Normal { .. }), "{:?}", events);
+    }
+
+    #[test]
+    fn test_twilight() {
+        // Paris, 2022-06-21: civil dawn 03:04 UTC, nautical dusk 21:41 UTC, no astronomical night
+        let date = NaiveDate::from_ymd_opt(2022, 6, 21).unwrap();
+        let events = |elevation| super::sun_events(date, 48.864716, 2.349014, elevation);
+
+        let SunEvents::Normal { rise, .. } = events(super::CIVIL_TWILIGHT) else {
+            panic!("civil twilight in Paris");
+        };
+        assert_close(rise, date.and_hms_opt(3, 4, 0).unwrap());
+
+        let SunEvents::Normal { set, .. } = events(super::NAUTICAL_TWILIGHT) else {
+            panic!("nautical twilight in Paris");
+        };
+        assert_close(set, date.and_hms_opt(21, 41, 0).unwrap());
+
+        assert_eq!(events(super::ASTRONOMICAL_TWILIGHT), SunEvents::AlwaysUp);
+    }
 
-    let jan_2000 = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
-    let time_since_2000: Duration = date.signed_duration_since(jan_2000);
-
-    let mean_solar_time = (time_since_2000.num_days() as f64) + 0.0008 - (longitude / 360.0);
-    let solar_mean_anomaly = (357.5291 + 0.98560028 * mean_solar_time) % 360.0;
-    let center = 1.9148 * solar_mean_anomaly.to_radians().sin()
-        + 0.0200 * (2.0 * solar_mean_anomaly).to_radians().sin()
-        + 0.0003 * (3.0 * solar_mean_anomaly).to_radians().sin();
-    let ecliptic_longitude = (solar_mean_anomaly + center + 180.0 + ARGUMENT_OF_PERIHELION) % 360.0;
-
-    let declination =
-        (ecliptic_longitude.to_radians().sin() * (23.44f64).to_radians().sin()).asin();
-    let hour_angle = (((-0.83 + elevation_correction).to_radians().sin()
-        - (latitude.to_radians().sin() * declination.sin()))
-        / (latitude.to_radians().cos() * declination.cos()))
-    .acos()
-    .to_degrees();
-
-    let solar_transit = mean_solar_time + 0.0053 * solar_mean_anomaly.to_radians().sin()
-        - 0.0069 * (2.0 * ecliptic_longitude).to_radians().sin();
-    println!("[!] solar_transit {}", solar_transit);
-
-    let solar_transit_date = jan_2000 + Duration::days(solar_transit.round() as i64);
-    println!("[!] solar_transit_date {}", solar_transit_date);
-
-    let solar_transit_date = solar_transit_date.and_hms_opt(12, 0, 0).unwrap();
-    // + Duration::seconds(
-    //     ((solar_transit * SECONDS_IN_A_DAY) % (SECONDS_IN_A_DAY)).round() as i64,
-    // );
-    println!("[!] solar_transit_date {}", solar_transit_date);
-
-    let minutes = Duration::minutes((hour_angle * HOUR_ANGLE_TO_MINUTES_FACTOR).round() as i64);
-    println!("[!] minutes offset {:?}", minutes);
-    let set = solar_transit_date + minutes;
-    let rise = solar_transit_date - minutes;
-    (rise, set)
+    #[test]
+    fn test_sun_times_at() {
+        // Los Angeles, late in the evening of 2022-01-01: the sunset of that day, in UTC the next
+        let datetime = NaiveDate::from_ymd_opt(2022, 1, 2)
+            .unwrap()
+            .and_hms_opt(5, 0, 0)
+            .unwrap();
+        let SunEvents::Normal { set, .. } = super::sun_times_at(datetime, LA_LAT, LA_LON, 0.0)
+        else {
+            panic!("the sun sets in Los Angeles");
+        };
+        assert_close(set, datetime.date().and_hms_opt(0, 55, 0).unwrap());
+    }
 }
@@ -0,0 +1,1289 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::{Marker, TScalarStyle};
+use yaml_rust::{ScanError, Yaml, YamlLoader};
+
+use builder::colors::{
+    describe, join_key, load_colors, load_tint, parse_color, Interpolation, PaletteError,
+    DEFAULT_BACKGROUND,
+};
+pub use builder::colors::{Color, Tint};
+
+use crate::angle;
+
+/// Theme used when none is asked for
+pub const DEFAULT_THEME: &str = "mountains";
+
+const MANIFEST_FILE: &str = "theme.yaml";
+const COLORS_FILE: &str = "colors.yaml";
+const LANDSCAPE_FILE: &str = "landscape.svg";
+const SUN_FILE: &str = "sun.svg";
+const STARS_FILE: &str = "stars.csv";
+
+const EMBEDDED_SUN: &str = include_str!("data/sun.svg");
+const EMBEDDED_STARS: &str = include_str!("data/stars.csv");
+
+/// A theme pack built into the binary, from the directories of `src/data/themes`
+struct Pack {
+    name: &'static str,
+    manifest: &'static str,
+    landscape: &'static str,
+    palette: &'static str,
+}
+
+macro_rules! pack {
+    ($name:literal) => {
+        Pack {
+            name: $name,
+            manifest: include_str!(concat!("data/themes/", $name, "/theme.yaml")),
+            landscape: include_str!(concat!("data/themes/", $name, "/landscape.svg")),
+            palette: include_str!(concat!("data/themes/", $name, "/colors.yaml")),
+        }
+    };
+}
+
+const PACKS: [Pack; 4] = [
+    pack!("mountains"),
+    pack!("sea"),
+    pack!("desert"),
+    pack!("city"),
+];
+
+/// The built-in theme pack of that name
+fn find_pack(name: &str) -> Option<&'static Pack> {
+    PACKS.iter().find(|pack| pack.name == name)
+}
+
+#[derive(Debug)]
+pub enum ThemeError {
+    Io(PathBuf, std::io::Error),
+    Yaml(String, ScanError),
+    Svg(String, usvg::Error),
+    Invalid(Invalid),
+    Unknown(String),
+}
+
+/// An invalid entry of a theme file
+#[derive(Debug)]
+pub struct Invalid {
+    pub file: String,
+    /// Starting from 1, when the entry can be found in the file
+    pub line: Option<usize>,
+    /// Where the entry is in the file, like `layers.4[2]`, empty for the whole file
+    pub key: String,
+    /// The entry as written, empty when missing
+    pub value: String,
+    pub message: String,
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ThemeError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            ThemeError::Yaml(file, e) => write!(f, "{}: invalid YAML: {}", file, e),
+            ThemeError::Svg(file, e) => write!(f, "{}: invalid SVG: {}", file, e),
+            ThemeError::Invalid(e) => write!(f, "{}", e),
+            ThemeError::Unknown(name) => write!(
+                f,
+                "no theme directory `{}`, the built-in themes are {}",
+                name,
+                Theme::builtin_names().collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Invalid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        write!(f, ": ")?;
+        if !self.key.is_empty() {
+            write!(f, "{}", self.key)?;
+            if !self.value.is_empty() {
+                write!(f, " `{}`", self.value)?;
+            }
+            write!(f, ": ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Star {
+    /// Expressed as degrees
+    pub right_ascension: f64,
+    /// Expressed as degrees
+    pub declination: f64,
+    pub magnitude: f64,
+}
+
+/// A path of the landscape, its id starts with the id of its layer (`4-2` is in layer 4)
+#[derive(Debug, Clone)]
+pub struct LandPath {
+    pub d: usvg::PathData,
+    pub id: String,
+    pub layer: String,
+    pub transform: usvg::Transform,
+}
+
+#[derive(Debug, Clone)]
+pub struct SunPath {
+    pub d: usvg::PathData,
+    pub opacity: Option<f64>,
+    pub fill: Color,
+}
+
+/// A bank of ground fog lying over the last path of a layer
+#[derive(Debug, Clone, PartialEq)]
+pub struct FogBank {
+    pub layer: String,
+    /// Expressed as scene pixels
+    pub top: f64,
+    /// Expressed as scene pixels
+    pub bottom: f64,
+}
+
+/// Colors of a landscape layer and the tints the seasons blend over them
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub id: String,
+    pub colors: Vec<Color>,
+    pub winter: Tint,
+    pub spring: Tint,
+    pub summer: Tint,
+    pub autumn: Tint,
+}
+
+/// Colors of the scene, each palette has one entry per degree of sun angle
+#[derive(Debug, Clone)]
+pub struct Palette {
+    pub sky_zenith: Vec<Color>,
+    pub sky_mid: Vec<Color>,
+    pub sky_horizon: Vec<Color>,
+    /// Landscape layers, from the farthest to the closest
+    pub layers: Vec<Layer>,
+    /// Position of each layer in `layers`, by id
+    index: HashMap<String, usize>,
+    /// Layers covered with snow in winter
+    pub snow_layers: Vec<String>,
+    /// How far the snow rises over the layers
+    pub snow_depth: f64,
+    /// Color space the sky gradient is blended in, when not the one of the renderer
+    pub interpolation: Option<Interpolation>,
+    /// Color the translucent colors are flattened over, also showing behind the scene in images
+    /// without transparency
+    pub background: Color,
+}
+
+impl Palette {
+    /// Looks a layer up by its id
+    pub fn layer(&self, id: &str) -> Option<&Layer> {
+        self.index.get(id).map(|&i| &self.layers[i])
+    }
+}
+
+/// Everything drawn in the scene that can change without rebuilding the binary
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    pub description: String,
+    pub palette: Palette,
+    pub landscape: Vec<LandPath>,
+    /// Height below which the scene is filled with the color of the closest layer
+    pub ground: Option<f64>,
+    pub fog: Vec<FogBank>,
+    pub sun: Vec<SunPath>,
+    pub stars: Vec<Star>,
+    /// Files missing from the theme directory, taken from the default theme instead
+    pub built_in: Vec<String>,
+}
+
+/// Description of a theme pack, the `theme.yaml` file of its directory
+#[derive(Debug)]
+struct Manifest {
+    name: String,
+    description: String,
+    /// The files of the pack, `None` for the ones named by default
+    landscape: Option<String>,
+    palette: Option<String>,
+    sun: Option<String>,
+    stars: Option<String>,
+    ground: Option<f64>,
+    fog: Vec<FogBank>,
+}
+
+const MANIFEST_KEYS: [&str; 8] = [
+    "name",
+    "description",
+    "landscape",
+    "palette",
+    "sun",
+    "stars",
+    "ground",
+    "fog",
+];
+
+const PALETTE_KEYS: [&str; 8] = [
+    "sky_zenith",
+    "sky_mid",
+    "sky_horizon",
+    "layers",
+    "seasons",
+    "snow",
+    "interpolation",
+    "background",
+];
+
+/// A file of a theme, along with the name its errors are reported under
+#[derive(Clone, Copy)]
+struct Source<'a> {
+    file: &'a str,
+    text: &'a str,
+}
+
+impl Source<'_> {
+    /// Reads the first document of a YAML file, a missing one for an empty file
+    fn yaml(&self) -> Result<Yaml, ThemeError> {
+        let mut docs = YamlLoader::load_from_str(self.text)
+            .map_err(|e| ThemeError::Yaml(self.file.to_string(), e))?;
+        if docs.is_empty() {
+            return Ok(Yaml::BadValue);
+        }
+        Ok(docs.swap_remove(0))
+    }
+
+    fn svg(&self) -> Result<usvg::Tree, ThemeError> {
+        let opt = usvg::Options::default();
+        usvg::Tree::from_data(self.text.as_bytes(), &opt.to_ref())
+            .map_err(|e| ThemeError::Svg(self.file.to_string(), e))
+    }
+
+    fn invalid_at(&self, line: Option<usize>, key: &str, value: &str, message: &str) -> ThemeError {
+        ThemeError::Invalid(Invalid {
+            file: self.file.to_string(),
+            line,
+            key: key.to_string(),
+            value: value.to_string(),
+            message: message.to_string(),
+        })
+    }
+
+    /// An invalid entry of a YAML file, found at `key`
+    fn invalid(&self, key: &str, value: &str, message: &str) -> ThemeError {
+        self.invalid_at(yaml_line(self.text, key), key, value, message)
+    }
+
+    fn palette_error(&self, e: PaletteError) -> ThemeError {
+        self.invalid(&e.key, &e.value, &e.message)
+    }
+}
+
+/// A collection being walked by `LineIndex`
+struct Open {
+    key: String,
+    list: bool,
+    /// Index of the next entry of a list
+    index: usize,
+    /// Key of a map awaiting its value
+    pending: Option<String>,
+}
+
+/// Notes the line each entry of a YAML file starts at, by its key
+struct LineIndex<'a> {
+    text: &'a str,
+    open: Vec<Open>,
+    lines: HashMap<String, usize>,
+}
+
+impl LineIndex<'_> {
+    /// Line of an event, an empty value being marked at the token after it rather than at the
+    /// `-` or `:` before it
+    fn line(&self, ev: &Event, mark: Marker) -> usize {
+        if let Event::Scalar(value, TScalarStyle::Plain, ..) = ev {
+            let mut chars = self.text.chars();
+            let before: String = chars.by_ref().take(mark.index()).collect();
+            if value == "~" && chars.next() != Some('~') {
+                return before.trim_end().lines().count();
+            }
+        }
+        mark.line()
+    }
+}
+
+impl MarkedEventReceiver for LineIndex<'_> {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        let list = match ev {
+            Event::Scalar(..) | Event::Alias(_) => None,
+            Event::SequenceStart(_) => Some(true),
+            Event::MappingStart(_) => Some(false),
+            Event::SequenceEnd | Event::MappingEnd => {
+                self.open.pop();
+                return;
+            }
+            _ => return,
+        };
+
+        let key = match self.open.last_mut() {
+            None => String::new(),
+            Some(parent) if parent.list => {
+                parent.index += 1;
+                join_key(&parent.key, &format!("[{}]", parent.index - 1))
+            }
+            Some(parent) => match parent.pending.take() {
+                Some(name) => join_key(&parent.key, &name),
+                // a key of the map, its entry starts here and its value comes next
+                None => {
+                    let name = match &ev {
+                        Event::Scalar(name, ..) => name.clone(),
+                        _ => "?".to_string(),
+                    };
+                    parent.pending = Some(name.clone());
+                    join_key(&parent.key, &name)
+                }
+            },
+        };
+
+        if !key.is_empty() {
+            let line = self.line(&ev, mark);
+            self.lines.entry(key.clone()).or_insert(line);
+        }
+        if let Some(list) = list {
+            self.open.push(Open {
+                key,
+                list,
+                index: 0,
+                pending: None,
+            });
+        }
+    }
+}
+
+/// Line of the entry at `key` of a YAML file, or else of the closest entry holding it
+fn yaml_line(text: &str, key: &str) -> Option<usize> {
+    let mut index = LineIndex {
+        text,
+        open: Vec::new(),
+        lines: HashMap::new(),
+    };
+    Parser::new(text.chars()).load(&mut index, false).ok()?;
+
+    let mut key = key;
+    while !key.is_empty() {
+        if let Some(&line) = index.lines.get(key) {
+            return Some(line);
+        }
+        key = &key[..key.rfind(['.', '[']).unwrap_or(0)];
+    }
+    None
+}
+
+/// Line of the element of an SVG file with the given id
+fn svg_line(text: &str, id: &str) -> Option<usize> {
+    let start = text
+        .find(&format!("id=\"{}\"", id))
+        .or_else(|| text.find(&format!("id='{}'", id)))?;
+    Some(text[..start].lines().count().max(1))
+}
+
+/// Rejects the keys of a YAML map that are not `known`, mostly typos
+fn check_keys(source: Source, doc: &Yaml, known: impl Fn(&str) -> bool) -> Result<(), ThemeError> {
+    let map = match doc {
+        Yaml::Hash(map) => map,
+        Yaml::BadValue => return Ok(()),
+        _ => return Err(source.invalid("", "", "expected a map")),
+    };
+
+    match map.keys().find(|key| !key.as_str().is_some_and(&known)) {
+        Some(key) => Err(source.invalid(&describe(key), "", "unknown key")),
+        None => Ok(()),
+    }
+}
+
+/// Reads a number written either as an integer or as a float
+fn as_number(value: &Yaml) -> Option<f64> {
+    value.as_f64().or_else(|| value.as_i64().map(|v| v as f64))
+}
+
+/// Reads an optional number of a YAML file, erring on anything else
+fn optional_number(source: Source, key: &str, value: &Yaml) -> Result<Option<f64>, ThemeError> {
+    match value {
+        Yaml::BadValue => Ok(None),
+        value => match as_number(value) {
+            Some(number) => Ok(Some(number)),
+            None => Err(source.invalid(key, &describe(value), "expected a number")),
+        },
+    }
+}
+
+fn parse_manifest(source: Source) -> Result<Manifest, ThemeError> {
+    let doc = source.yaml()?;
+    check_keys(source, &doc, |key| MANIFEST_KEYS.contains(&key))?;
+
+    let optional_text = |key: &str| match &doc[key] {
+        Yaml::BadValue => Ok(None),
+        Yaml::String(text) => Ok(Some(text.clone())),
+        value => Err(source.invalid(key, &describe(value), "expected a text")),
+    };
+    let text = |key: &str, default: &str| {
+        optional_text(key).map(|text| text.unwrap_or_else(|| default.to_string()))
+    };
+
+    let banks = match &doc["fog"] {
+        Yaml::BadValue => &[][..],
+        Yaml::Array(banks) => banks.as_slice(),
+        value => {
+            return Err(source.invalid("fog", &describe(value), "expected a list of fog banks"))
+        }
+    };
+    let fog = banks
+        .iter()
+        .enumerate()
+        .map(|(i, bank)| {
+            let key = format!("fog[{}]", i);
+            let layer = match layer_id(&bank["layer"]) {
+                Some(layer) => layer,
+                None => {
+                    return Err(source.invalid(
+                        &join_key(&key, "layer"),
+                        &describe(&bank["layer"]),
+                        "expected a layer id",
+                    ))
+                }
+            };
+            let top = optional_number(source, &join_key(&key, "top"), &bank["top"])?;
+            let bottom = optional_number(source, &join_key(&key, "bottom"), &bank["bottom"])?;
+
+            match (top, bottom) {
+                (Some(top), Some(bottom)) if top < bottom => Ok(FogBank { layer, top, bottom }),
+                (Some(_), Some(_)) => {
+                    Err(source.invalid(&key, "", "the top of a fog bank must be above its bottom"))
+                }
+                _ => Err(source.invalid(&key, "", "a fog bank needs a top and a bottom")),
+            }
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(Manifest {
+        name: text("name", "")?,
+        description: text("description", "")?,
+        landscape: optional_text("landscape")?,
+        palette: optional_text("palette")?,
+        sun: optional_text("sun")?,
+        stars: optional_text("stars")?,
+        ground: optional_number(source, "ground", &doc["ground"])?,
+        fog,
+    })
+}
+
+impl Theme {
+    /// Names of the themes built into the binary
+    pub fn builtin_names() -> impl Iterator<Item = &'static str> {
+        PACKS.iter().map(|pack| pack.name)
+    }
+
+    /// A theme built into the binary, `None` if there is none with that name
+    pub fn builtin(name: &str) -> Option<Self> {
+        let pack = find_pack(name)?;
+
+        let files = [MANIFEST_FILE, COLORS_FILE, LANDSCAPE_FILE]
+            .map(|file| format!("{}/{}", pack.name, file));
+        let manifest = Source {
+            file: &files[0],
+            text: pack.manifest,
+        };
+        let theme = parse_manifest(manifest).and_then(|parsed| {
+            Self::parse(
+                manifest,
+                parsed,
+                Source {
+                    file: &files[1],
+                    text: pack.palette,
+                },
+                Source {
+                    file: &files[2],
+                    text: pack.landscape,
+                },
+                Source {
+                    file: SUN_FILE,
+                    text: EMBEDDED_SUN,
+                },
+                Source {
+                    file: STARS_FILE,
+                    text: EMBEDDED_STARS,
+                },
+            )
+        });
+        Some(theme.expect("the built-in themes are valid"))
+    }
+
+    /// The theme drawn by default
+    pub fn embedded() -> Self {
+        Self::builtin(DEFAULT_THEME).unwrap()
+    }
+
+    /// Picks a built-in theme by its name, or else loads the theme pack of a directory
+    pub fn select(name: &str) -> Result<Self, ThemeError> {
+        if let Some(theme) = Self::builtin(name) {
+            return Ok(theme);
+        }
+
+        let dir = Path::new(name);
+        if !dir.is_dir() {
+            return Err(ThemeError::Unknown(name.to_string()));
+        }
+        Self::load(dir)
+    }
+
+    /// Loads the theme pack of a directory
+    ///
+    /// The `theme.yaml` manifest names the landscape, the palette and, optionally, the sun and
+    /// the star catalog. Any of these files missing from the directory, the manifest included,
+    /// is taken from the default theme, unless the manifest names it.
+    pub fn load(dir: &Path) -> Result<Self, ThemeError> {
+        if !dir.is_dir() {
+            let e = std::io::Error::new(std::io::ErrorKind::NotFound, "not a theme directory");
+            return Err(ThemeError::Io(dir.to_path_buf(), e));
+        }
+        let default = find_pack(DEFAULT_THEME).expect("the default theme is built in");
+
+        // each file along with the name its errors are reported under
+        let mut built_in = Vec::new();
+        let mut read = |name: &str, embedded: &str| -> Result<(String, String), ThemeError> {
+            let path = dir.join(name);
+            if path.exists() {
+                log::debug!("Reading {}", path.display());
+                match std::fs::read_to_string(&path) {
+                    Ok(text) => Ok((path.display().to_string(), text)),
+                    Err(e) => Err(ThemeError::Io(path, e)),
+                }
+            } else {
+                log::debug!("No {} in {}, using the built-in one", name, dir.display());
+                built_in.push(name.to_string());
+                Ok((format!("built-in {}", name), embedded.to_string()))
+            }
+        };
+        fn source((file, text): &(String, String)) -> Source<'_> {
+            Source { file, text }
+        }
+
+        let own_manifest = dir.join(MANIFEST_FILE).exists();
+        let manifest_file = read(MANIFEST_FILE, default.manifest)?;
+        let manifest = parse_manifest(source(&manifest_file))?;
+
+        // only the files the manifest leaves unnamed may be missing
+        let named = |key: &str, name: &Option<String>, default: &str| match name {
+            Some(name) if own_manifest && !dir.join(name).exists() => Err(
+                source(&manifest_file).invalid(key, name, "no such file in the theme directory")
+            ),
+            Some(name) if own_manifest => Ok(name.clone()),
+            _ => Ok(default.to_string()),
+        };
+        let palette = named("palette", &manifest.palette, COLORS_FILE)?;
+        let landscape = named("landscape", &manifest.landscape, LANDSCAPE_FILE)?;
+        let sun = named("sun", &manifest.sun, SUN_FILE)?;
+        let stars = named("stars", &manifest.stars, STARS_FILE)?;
+
+        let palette = read(&palette, default.palette)?;
+        let landscape = read(&landscape, default.landscape)?;
+        let sun = read(&sun, EMBEDDED_SUN)?;
+        let stars = read(&stars, EMBEDDED_STARS)?;
+
+        let theme = Self::parse(
+            source(&manifest_file),
+            manifest,
+            source(&palette),
+            source(&landscape),
+            source(&sun),
+            source(&stars),
+        )?;
+        Ok(Self { built_in, ..theme })
+    }
+
+    /// Loads the theme pack of a directory to validate it, the directory holding at least one of
+    /// the files of a pack
+    pub fn check(dir: &Path) -> Result<Self, ThemeError> {
+        let theme = Self::load(dir)?;
+
+        let files = [
+            MANIFEST_FILE,
+            COLORS_FILE,
+            LANDSCAPE_FILE,
+            SUN_FILE,
+            STARS_FILE,
+        ];
+        if files
+            .iter()
+            .all(|file| theme.built_in.iter().any(|b| b == file))
+        {
+            let e = std::io::Error::new(std::io::ErrorKind::NotFound, "no theme file found");
+            return Err(ThemeError::Io(dir.to_path_buf(), e));
+        }
+        Ok(theme)
+    }
+
+    fn parse(
+        manifest_file: Source,
+        manifest: Manifest,
+        colors: Source,
+        landscape_file: Source,
+        sun: Source,
+        stars: Source,
+    ) -> Result<Self, ThemeError> {
+        let palette = parse_palette(colors)?;
+        let landscape = parse_landscape(landscape_file)?;
+
+        // every layer drawn must have its colors
+        if let Some(path) = landscape.iter().find(|p| palette.layer(&p.layer).is_none()) {
+            return Err(landscape_file.invalid_at(
+                svg_line(landscape_file.text, &path.id),
+                "id",
+                &path.id,
+                &format!("path in the undefined layer `{}`", path.layer),
+            ));
+        }
+        if let Some((i, bank)) = manifest
+            .fog
+            .iter()
+            .enumerate()
+            .find(|(_, b)| palette.layer(&b.layer).is_none())
+        {
+            return Err(manifest_file.invalid(
+                &format!("fog[{}].layer", i),
+                &bank.layer,
+                "fog bank over an undefined layer",
+            ));
+        }
+
+        Ok(Self {
+            name: manifest.name,
+            description: manifest.description,
+            palette,
+            landscape,
+            ground: manifest.ground,
+            fog: manifest.fog,
+            sun: parse_sun(sun)?,
+            stars: parse_stars(stars)?,
+            built_in: Vec::new(),
+        })
+    }
+}
+
+/// Reads a layer id, written either as a number or as a name
+fn layer_id(key: &Yaml) -> Option<String> {
+    match key {
+        Yaml::Integer(id) => Some(id.to_string()),
+        Yaml::String(id) => Some(id.clone()),
+        _ => None,
+    }
+}
+
+fn parse_palette(source: Source) -> Result<Palette, ThemeError> {
+    let gradient_names = source.yaml()?;
+    check_keys(source, &gradient_names, |key| {
+        PALETTE_KEYS.contains(&key)
+            || key
+                .strip_prefix("layer")
+                .is_some_and(|i| i.parse::<usize>().is_ok())
+    })?;
+
+    let interpolation = match &gradient_names["interpolation"] {
+        Yaml::BadValue => None,
+        name => match name.as_str().and_then(Interpolation::parse) {
+            Some(interpolation) => Some(interpolation),
+            None => {
+                return Err(source.invalid("interpolation", &describe(name), "unknown color space"))
+            }
+        },
+    };
+    // translucent colors are flattened over the background, the night sky unless declared
+    let background = match &gradient_names["background"] {
+        Yaml::BadValue => DEFAULT_BACKGROUND,
+        code => match code.as_str().and_then(parse_color) {
+            Some(background) => background,
+            None => {
+                return Err(source.invalid("background", &describe(code), "expected a CSS color"))
+            }
+        },
+    };
+    let colors = |key: &str, data: &Yaml| {
+        load_colors(
+            data,
+            &angle::elevation_angle,
+            interpolation.unwrap_or_default(),
+            background,
+        )
+        .map_err(|e| source.palette_error(e.within(key)))
+    };
+
+    // layers are either a map of palettes by id, or a list of palettes numbered from 1; older
+    // palettes declare them as `layer1`, `layer2`... and key their tints the same way
+    let legacy = gradient_names["layers"].is_badvalue();
+    let declared: Vec<(String, String, &Yaml)> = match &gradient_names["layers"] {
+        Yaml::Hash(layers) => layers
+            .iter()
+            .map(|(key, colors)| match layer_id(key) {
+                Some(id) => Ok((id.clone(), join_key("layers", &id), colors)),
+                None => Err(source.invalid("layers", &describe(key), "expected a layer id")),
+            })
+            .collect::<Result<_, _>>()?,
+        Yaml::Array(layers) => layers
+            .iter()
+            .enumerate()
+            .map(|(i, colors)| ((i + 1).to_string(), format!("layers[{}]", i), colors))
+            .collect(),
+        Yaml::BadValue => {
+            let mut numbered: Vec<(usize, String, &Yaml)> = gradient_names
+                .as_hash()
+                .into_iter()
+                .flatten()
+                .filter_map(|(key, colors)| {
+                    let key = key.as_str()?;
+                    let number = key.strip_prefix("layer")?.parse().ok()?;
+                    Some((number, key.to_string(), colors))
+                })
+                .collect();
+            numbered.sort_by_key(|(number, _, _)| *number);
+            // a layer skipped in the numbering is more likely a typo than a wish
+            for (expected, (number, key, _)) in (1..).zip(&numbered) {
+                if *number == 0 {
+                    return Err(source.invalid(key, "", "layers are numbered from 1"));
+                }
+                if *number > expected {
+                    let message = format!("no `layer{}` before it", expected);
+                    return Err(source.invalid(key, "", &message));
+                }
+            }
+            numbered
+                .into_iter()
+                .map(|(number, key, colors)| (number.to_string(), key, colors))
+                .collect()
+        }
+        value => {
+            return Err(source.invalid(
+                "layers",
+                &describe(value),
+                "expected a map or a list of palettes",
+            ))
+        }
+    };
+    if declared.is_empty() {
+        return Err(source.invalid("layers", "", "no landscape layer"));
+    }
+
+    let mut layers = Vec::new();
+    let mut index = HashMap::new();
+    for (id, key, palette) in declared {
+        if index.insert(id.clone(), layers.len()).is_some() {
+            return Err(source.invalid(&key, &id, "layer declared twice"));
+        }
+        layers.push(Layer {
+            id,
+            colors: colors(&key, palette)?,
+            winter: Tint::default(),
+            spring: Tint::default(),
+            summer: Tint::default(),
+            autumn: Tint::default(),
+        });
+    }
+
+    let seasons = match &gradient_names["seasons"] {
+        Yaml::BadValue => Default::default(),
+        Yaml::Hash(seasons) => seasons.clone(),
+        value => return Err(source.invalid("seasons", &describe(value), "expected a map")),
+    };
+    for (season, tints) in &seasons {
+        let season_key = join_key("seasons", &describe(season));
+        let tints = match (season.as_str(), tints) {
+            (Some("winter" | "spring" | "summer" | "autumn"), Yaml::Hash(tints)) => tints,
+            (Some("winter" | "spring" | "summer" | "autumn"), _) => {
+                return Err(source.invalid(
+                    &season_key,
+                    &describe(tints),
+                    "expected a map of tints by layer",
+                ))
+            }
+            _ => return Err(source.invalid(&season_key, "", "unknown season")),
+        };
+
+        for (key, tint) in tints {
+            let tint_key = join_key(&season_key, &describe(key));
+            let id = layer_id(key).unwrap_or_default();
+            let id = match id.strip_prefix("layer") {
+                Some(number) if legacy => number.to_string(),
+                _ => id,
+            };
+            let layer = match index.get(&id) {
+                Some(&i) => &mut layers[i],
+                None => {
+                    return Err(source.invalid(
+                        &tint_key,
+                        &describe(key),
+                        "tint of an undefined layer",
+                    ))
+                }
+            };
+
+            let tint = load_tint(tint, background)
+                .map_err(|e| source.palette_error(e.within(&tint_key)))?;
+            match season.as_str() {
+                Some("winter") => layer.winter = tint,
+                Some("spring") => layer.spring = tint,
+                Some("summer") => layer.summer = tint,
+                _ => layer.autumn = tint,
+            }
+        }
+    }
+
+    let snow = &gradient_names["snow"];
+    let snow_layers = match &snow["layers"] {
+        Yaml::BadValue => Vec::new(),
+        Yaml::Array(ids) => ids.as_slice().to_vec(),
+        value => {
+            return Err(source.invalid(
+                "snow.layers",
+                &describe(value),
+                "expected a list of layer ids",
+            ))
+        }
+    };
+    let snow_layers = snow_layers
+        .iter()
+        .enumerate()
+        .map(|(i, id)| match layer_id(id) {
+            Some(id) if index.contains_key(&id) => Ok(id),
+            _ => Err(source.invalid(
+                &format!("snow.layers[{}]", i),
+                &describe(id),
+                "snow on an undefined layer",
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let snow_depth = optional_number(source, "snow.depth", &snow["depth"])?.unwrap_or(0.0);
+
+    Ok(Palette {
+        sky_zenith: colors("sky_zenith", &gradient_names["sky_zenith"])?,
+        sky_mid: colors("sky_mid", &gradient_names["sky_mid"])?,
+        sky_horizon: colors("sky_horizon", &gradient_names["sky_horizon"])?,
+        layers,
+        index,
+        snow_layers,
+        snow_depth,
+        interpolation,
+        background,
+    })
+}
+
+fn parse_landscape(source: Source) -> Result<Vec<LandPath>, ThemeError> {
+    let rtree = source.svg()?;
+
+    let mut landscape = Vec::new();
+    for node in rtree.root().children() {
+        if let usvg::NodeKind::Path(path) = &*node.borrow() {
+            landscape.push(LandPath {
+                d: (*path.data).clone(),
+                id: path.id.clone(),
+                layer: path.id.split('-').next().unwrap_or_default().to_string(),
+                transform: path.transform,
+            });
+        }
+    }
+
+    Ok(landscape)
+}
+
+fn sun_path(path: &usvg::Path, opacity: Option<f64>) -> SunPath {
+    let fill = match path.fill.as_ref().map(|fill| &fill.paint) {
+        Some(usvg::Paint::Color(c)) => Color {
+            r: c.red,
+            g: c.green,
+            b: c.blue,
+        },
+        _ => Color { r: 0, g: 0, b: 0 },
+    };
+
+    SunPath {
+        d: (*path.data).clone(),
+        opacity,
+        fill,
+    }
+}
+
+fn parse_sun(source: Source) -> Result<Vec<SunPath>, ThemeError> {
+    let rtree = source.svg()?;
+
+    let mut sun = Vec::new();
+    for node in rtree.root().children() {
+        match &*node.borrow() {
+            usvg::NodeKind::Path(path) => sun.push(sun_path(path, None)),
+            usvg::NodeKind::Group(group) => {
+                let opacity = group.opacity.value();
+
+                if let Some(child) = node.first_child() {
+                    if let usvg::NodeKind::Path(path) = &*child.borrow() {
+                        sun.push(sun_path(path, Some(opacity)));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(sun)
+}
+
+/// Reads the star catalog, one `name,right ascension (hours),declination,magnitude` per line
+fn parse_stars(source: Source) -> Result<Vec<Star>, ThemeError> {
+    let mut stars = Vec::new();
+
+    for (number, line) in source.text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |key: &str, value: &str, message: &str| {
+            source.invalid_at(Some(number + 1), key, value, message)
+        };
+
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if fields.len() != 4 {
+            return Err(invalid(
+                "",
+                "",
+                "expected a name, a right ascension, a declination and a magnitude",
+            ));
+        }
+
+        let mut values = [0.0; 3];
+        for ((value, field), key) in
+            values
+                .iter_mut()
+                .zip(&fields[1..])
+                .zip(["right ascension", "declination", "magnitude"])
+        {
+            *value = field
+                .parse()
+                .map_err(|_| invalid(key, field, "expected a number"))?;
+        }
+        let [hours, declination, magnitude] = values;
+
+        if !(0.0..24.0).contains(&hours) {
+            return Err(invalid(
+                "right ascension",
+                fields[1],
+                "expected hours from 0 to 24",
+            ));
+        }
+        if !(-90.0..=90.0).contains(&declination) {
+            return Err(invalid(
+                "declination",
+                fields[2],
+                "expected degrees from -90 to 90",
+            ));
+        }
+
+        stars.push(Star {
+            right_ascension: hours * 15.0,
+            declination,
+            magnitude,
+        });
+    }
+
+    Ok(stars)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{Palette, Source, Theme, ThemeError};
+
+    fn read_palette(text: &str) -> Result<Palette, ThemeError> {
+        super::parse_palette(Source {
+            file: "colors.yaml",
+            text,
+        })
+    }
+
+    /// The message of the error reading a palette
+    fn palette_error(text: &str) -> String {
+        read_palette(text).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_builtin() {
+        let theme = Theme::embedded();
+        assert_eq!(theme.palette.layers.len(), 11);
+        assert_eq!(theme.palette.layer("11").unwrap().winter.strength, 0.35);
+        assert_eq!(theme.fog.len(), 3);
+        assert!(theme.stars.len() > 200);
+
+        for name in Theme::builtin_names() {
+            let theme = Theme::builtin(name).unwrap();
+            assert!(!theme.name.is_empty(), "{}", name);
+            assert!(!theme.landscape.is_empty(), "{}", name);
+            assert!(!theme.sun.is_empty(), "{}", name);
+            assert!(theme.palette.layers.iter().all(|l| l.colors.len() == 360));
+        }
+        assert!(Theme::builtin("moon").is_none());
+    }
+
+    const SKY: &str = "
+sky_zenith: ['#000']
+sky_mid: ['#000']
+sky_horizon: ['#000']
+";
+
+    #[test]
+    fn test_layers() {
+        // a map of layers by id
+        let palette = read_palette(&format!(
+            "{}layers: {{ far: ['#fff'], 2: ['#000'] }}\nseasons: {{ winter: {{ far: {{ color: '#fff', strength: 0.5 }} }} }}",
+            SKY
+        ))
+        .unwrap();
+        let ids: Vec<&str> = palette.layers.iter().map(|l| l.id.as_str()).collect();
+        assert_eq!(ids, ["far", "2"]);
+        assert_eq!(palette.layer("far").unwrap().winter.strength, 0.5);
+        assert!(palette.layer("3").is_none());
+
+        // a list of layers numbered from 1, and the older `layerN` keys
+        let palette = read_palette(&format!("{}layers: [['#fff'], ['#000']]", SKY)).unwrap();
+        assert!(palette.layer("2").is_some());
+        let palette = read_palette(&format!("{}layer1: ['#fff']\nlayer2: ['#000']", SKY)).unwrap();
+        assert!(palette.layer("2").is_some());
+        let gap = format!(
+            "{}layer1: ['#fff']\nlayer2: ['#000']\nlayer4: ['#000']",
+            SKY
+        );
+        assert_eq!(
+            read_palette(&gap).unwrap_err().to_string(),
+            "colors.yaml:7: layer4: no `layer3` before it"
+        );
+
+        // tints and snow on undefined layers
+        let palette = format!("{}layers: [['#fff']]", SKY);
+        assert!(read_palette(&format!("{}\nsnow: {{ layers: [2] }}", palette)).is_err());
+        assert!(read_palette(&format!(
+            "{}\nseasons: {{ autumn: {{ 2: {{ color: '#fff' }} }} }}",
+            palette
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn test_keyframes() {
+        let palette = read_palette(
+            "
+sky_zenith:
+  - { at: 0, color: '#000000' }
+  - { at: 180, color: '#ffffff', ease: step }
+sky_mid:
+  - { elevation: -0.833, color: '#ffffff' }
+  - { elevation: -45, color: '#000000' }
+sky_horizon: ['#000']
+layers: [['#000']]
+",
+        )
+        .unwrap();
+
+        let zenith = &palette.sky_zenith;
+        assert_eq!(zenith.len(), 360);
+        assert_eq!(zenith[90].r, 128);
+        // the step holds the white stop until it comes back round to the black one
+        assert_eq!(zenith[359].r, 255);
+
+        // the sun reaches the horizon at 90 while rising and 270 while setting
+        let mid = &palette.sky_mid;
+        assert_eq!(
+            (mid[0].r, mid[90].r, mid[180].r, mid[270].r),
+            (0, 255, 255, 255)
+        );
+        assert!(mid[45].r > 0 && mid[45].r < 255);
+    }
+
+    #[test]
+    fn test_interpolation() {
+        // from the dusk pink to the day blue
+        let palette = read_palette(
+            "
+interpolation: oklch
+sky_zenith: ['#ef509a', '#0089b9']
+sky_mid: { interpolation: srgb, colors: ['#ef509a', '#0089b9'] }
+sky_horizon: ['#000']
+layers: [['#000']]
+",
+        )
+        .unwrap();
+
+        // the perceptual blend keeps the midpoint saturated
+        let chroma = |c: &super::Color| c.r.max(c.g).max(c.b) - c.r.min(c.g).min(c.b);
+        let perceptual = &palette.sky_zenith[180];
+        let plain = &palette.sky_mid[180];
+        assert!(
+            chroma(perceptual) > chroma(plain),
+            "{:?} {:?}",
+            perceptual,
+            plain
+        );
+        assert_eq!(palette.sky_zenith[0], palette.sky_mid[0]);
+        assert!(palette.interpolation.is_some());
+
+        assert!(read_palette("interpolation: cmyk\nlayers: [['#000']]").is_err());
+    }
+
+    #[test]
+    fn test_background() {
+        let palette = read_palette(
+            "
+background: '#ffffff'
+sky_zenith: ['rgba(0,0,0,0.5)']
+sky_mid: { background: '#000000', colors: ['rgba(255,255,255,0.5)'] }
+sky_horizon: ['rgba(0,0,0,0.5)']
+layers: { 1: { background: '#ff0000', colors: ['rgba(0,0,255,0.5)'] }, 2: ['#00ff00'] }
+",
+        )
+        .unwrap();
+
+        let grey = super::Color {
+            r: 128,
+            g: 128,
+            b: 128,
+        };
+        assert_eq!(palette.sky_zenith[0], grey);
+        assert_eq!(palette.sky_mid[0], grey);
+        assert_eq!(
+            palette.layers[0].colors[0],
+            super::Color {
+                r: 128,
+                g: 0,
+                b: 128
+            }
+        );
+
+        // palettes declaring no background are flattened over the night sky
+        let palette = read_palette(&format!("{}layers: [['rgba(0,0,0,0)']]", SKY));
+        assert_eq!(
+            palette.unwrap().layers[0].colors[0],
+            super::DEFAULT_BACKGROUND
+        );
+
+        assert!(read_palette("background: navyish\nlayers: [['#000']]").is_err());
+    }
+
+    #[test]
+    fn test_load() {
+        // a pack directory, the sun and the stars are taken from the default theme
+        let theme = Theme::load(Path::new("src/data/themes/sea")).unwrap();
+        assert_eq!(theme.name, "Sea");
+        assert_eq!(theme.palette.layers.len(), 6);
+        assert_eq!(theme.stars.len(), Theme::embedded().stars.len());
+        assert_eq!(theme.built_in, ["sun.svg", "stars.csv"]);
+
+        // every file falls back to the default theme
+        let theme = Theme::load(Path::new("src")).unwrap();
+        assert_eq!(theme.name, "Mountains");
+        assert_eq!(theme.built_in.len(), 5);
+        assert!(Theme::check(Path::new("src")).is_err());
+        assert!(Theme::check(Path::new("src/data/themes/sea")).is_ok());
+
+        assert!(Theme::select("desert").is_ok());
+        assert!(Theme::select("no/such/theme").is_err());
+
+        // a file named by the manifest must be there
+        let dir = std::env::temp_dir().join(format!("goldvalley-theme-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("theme.yaml"),
+            "name: Typo\npalette: colours.yaml\n",
+        )
+        .unwrap();
+        let error = Theme::load(&dir).unwrap_err().to_string();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            error,
+            format!(
+                "{}:2: palette `colours.yaml`: no such file in the theme directory",
+                dir.join("theme.yaml").display()
+            )
+        );
+    }
+
+    #[test]
+    fn test_validation() {
+        let source = |file, text| Source { file, text };
+
+        assert_eq!(
+            palette_error(&format!("{}layers:\n  1: ['#fff', '#ggg']", SKY)),
+            "colors.yaml:6: layers.1[1] `#ggg`: expected a CSS color"
+        );
+        assert_eq!(
+            palette_error(&format!("{}layers:\n  - - '#fff'\n    -\n", SKY)),
+            "colors.yaml:7: layers[0][1] `~`: expected a CSS color"
+        );
+        assert_eq!(
+            palette_error(&format!(
+                "{}layers: [[{{ at: 0, color: '#fff', ease: bounce }}]]",
+                SKY
+            )),
+            "colors.yaml:5: layers[0][0].ease `bounce`: unknown easing"
+        );
+        assert_eq!(
+            palette_error("sky_zenith: ['#000']\nlayers: [['#000']]"),
+            "colors.yaml: sky_mid: expected a list of colors"
+        );
+        assert_eq!(
+            palette_error(&format!("{}layers: [['#000']]\nsky_zentih: []", SKY)),
+            "colors.yaml:6: sky_zentih: unknown key"
+        );
+        assert_eq!(
+            palette_error(&format!(
+                "{}layers: [['#000']]\nseasons:\n  winter:\n    2: {{ color: '#fff' }}",
+                SKY
+            )),
+            "colors.yaml:8: seasons.winter.2 `2`: tint of an undefined layer"
+        );
+
+        let manifest = |text| super::parse_manifest(source("theme.yaml", text));
+        assert_eq!(
+            manifest("name: Valley\nfog:\n  - { layer: 2 }")
+                .unwrap_err()
+                .to_string(),
+            "theme.yaml:3: fog[0]: a fog bank needs a top and a bottom"
+        );
+        assert!(manifest("ground: low").is_err());
+
+        let stars = |text| super::parse_stars(source("stars.csv", text));
+        assert_eq!(
+            stars("# name,ra,dec,mag\nSirius,6.75,-16.7")
+                .unwrap_err()
+                .to_string(),
+            "stars.csv:2: expected a name, a right ascension, a declination and a magnitude"
+        );
+        assert_eq!(
+            stars("Sirius,6.75,-96.7,-1.46").unwrap_err().to_string(),
+            "stars.csv:1: declination `-96.7`: expected degrees from -90 to 90"
+        );
+
+        // a path in a layer the palette does not declare
+        let landscape = r#"<svg viewBox="0 0 10 10" xmlns="http://www.w3.org/2000/svg">
+            <path id="12-1" d="M 0 0 L 10 10 L 0 10 Z"/></svg>"#;
+        let pack = &super::PACKS[0];
+        let theme = Theme::parse(
+            source("theme.yaml", pack.manifest),
+            manifest(pack.manifest).unwrap(),
+            source("colors.yaml", pack.palette),
+            source("landscape.svg", landscape),
+            source("sun.svg", super::EMBEDDED_SUN),
+            source("stars.csv", super::EMBEDDED_STARS),
+        );
+        assert_eq!(
+            theme.unwrap_err().to_string(),
+            "landscape.svg:2: id `12-1`: path in the undefined layer `12`"
+        );
+    }
+}
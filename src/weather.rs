@@ -0,0 +1,176 @@
+/// Presets of the conditions drawn over the scene
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Weather {
+    #[default]
+    Clear,
+    Cloudy,
+    Overcast,
+    Fog,
+    Rain,
+    Snow,
+}
+
+/// One round blob of a cloud, in scene coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Puff {
+    pub x: f64,
+    pub y: f64,
+    pub rx: f64,
+    pub ry: f64,
+}
+
+/// A falling raindrop or snowflake, in scene coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Particle {
+    pub x: f64,
+    pub y: f64,
+    /// Length of a raindrop streak or radius of a snowflake
+    pub size: f64,
+    pub opacity: f64,
+}
+
+/// Small deterministic generator (SplitMix64), the same seed always draws the same weather
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform number in `[0, 1)`
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform number in `[low, high)`
+    fn range(&mut self, low: f64, high: f64) -> f64 {
+        low + (high - low) * self.next_f64()
+    }
+}
+
+/// What falls from the clouds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precipitation {
+    Rain,
+    Snow,
+}
+
+/// Everything the renderer needs to know about the weather
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Conditions {
+    /// Fraction of the sky hidden by clouds, from 0 to 1
+    pub cloud_cover: f64,
+    /// Density of the ground fog between the landscape layers, from 0 to 1
+    pub fog: f64,
+    pub precipitation: Option<Precipitation>,
+    /// How hard it rains or snows, from 0 to 1
+    pub intensity: f64,
+    /// How much the grey weather washes the colors of the sky out, from 0 to 1
+    pub desaturation: f64,
+}
+
+impl Default for Conditions {
+    fn default() -> Self {
+        Weather::Clear.into()
+    }
+}
+
+impl From<Weather> for Conditions {
+    fn from(weather: Weather) -> Self {
+        let (cloud_cover, fog, precipitation, desaturation) = match weather {
+            Weather::Clear => (0.0, 0.0, None, 0.0),
+            Weather::Cloudy => (0.35, 0.0, None, 0.1),
+            Weather::Overcast => (0.9, 0.15, None, 0.4),
+            Weather::Fog => (0.3, 0.9, None, 0.3),
+            Weather::Rain => (0.9, 0.3, Some(Precipitation::Rain), 0.5),
+            Weather::Snow => (0.8, 0.3, Some(Precipitation::Snow), 0.4),
+        };
+
+        Self {
+            cloud_cover,
+            fog,
+            precipitation,
+            intensity: 0.5,
+            desaturation,
+        }
+    }
+}
+
+impl Conditions {
+    /// Generates the cloud banks, each one a list of puffs
+    pub fn clouds(&self, seed: u64) -> Vec<Vec<Puff>> {
+        let count = (self.cloud_cover * 18.0).round() as usize;
+
+        let mut rng = Rng::new(seed);
+        (0..count)
+            .map(|_| {
+                // banks gather toward the horizon and look smaller there
+                let y = 60.0 + rng.next_f64().powf(0.7) * 380.0;
+                let scale = 1.3 - 0.8 * y / 540.0;
+                let x = rng.range(-100.0, 1700.0);
+                let width = rng.range(120.0, 320.0) * scale;
+
+                let puffs = rng.range(5.0, 10.0) as usize;
+                (0..puffs)
+                    .map(|_| {
+                        let rx = rng.range(24.0, 56.0) * scale;
+                        Puff {
+                            x: x + rng.range(-0.5, 0.5) * width,
+                            y: y - rng.range(0.0, 0.4) * rx,
+                            rx,
+                            ry: rx * 0.6,
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Generates the rain or snow falling in front of the scene
+    pub fn particles(&self, seed: u64) -> Vec<Particle> {
+        let (count, size) = match self.precipitation {
+            Some(Precipitation::Rain) => (840.0, (12.0, 26.0)),
+            Some(Precipitation::Snow) => (640.0, (1.0, 3.2)),
+            None => return Vec::new(),
+        };
+        let count = (count * self.intensity.clamp(0.0, 1.0)).round() as usize;
+
+        // a different stream from the clouds, so both can change independently
+        let mut rng = Rng::new(seed ^ 0x5eed_5eed_5eed_5eed);
+        (0..count)
+            .map(|_| Particle {
+                x: rng.range(0.0, 1600.0),
+                y: rng.range(0.0, 900.0),
+                size: rng.range(size.0, size.1),
+                opacity: rng.range(0.25, 0.75),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Conditions, Weather};
+
+    #[test]
+    fn test_deterministic() {
+        let cloudy = Conditions::from(Weather::Cloudy);
+        assert_eq!(cloudy.clouds(42), cloudy.clouds(42));
+        assert_ne!(cloudy.clouds(42), cloudy.clouds(43));
+        assert!(cloudy.particles(42).is_empty());
+
+        let snow = Conditions::from(Weather::Snow);
+        assert_eq!(snow.particles(7), snow.particles(7));
+
+        assert!(Conditions::default().clouds(42).is_empty());
+    }
+}